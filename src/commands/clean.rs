@@ -0,0 +1,67 @@
+//! Implementation of the `clean` command.
+
+use crate::{
+    commands::generate::{docset_names, docset_output_dir},
+    error::*
+};
+
+use cargo::{core::Workspace, Config as CargoConfig};
+use snafu::ResultExt;
+
+use std::{
+    fs::{read_dir, remove_dir, remove_dir_all, remove_file},
+    path::{is_separator, Path}
+};
+
+/// Remove the docset named `docset_name` located in `dir`, along with the archive and the feed
+/// written next to it, and report the removed paths. Return whether anything was removed.
+fn remove_docset(cargo_cfg: &CargoConfig, dir: &Path, docset_name: &str) -> Result<bool> {
+    let mut removed = false;
+    let docset_dir = dir.join(format!("{}.docset", docset_name));
+    if docset_dir.is_dir() {
+        remove_dir_all(&docset_dir).context(IoWrite)?;
+        cargo_cfg.shell().status("Removed", docset_dir.display()).context(Cargo)?;
+        removed = true;
+    }
+    for extension in &["tgz", "xml"] {
+        let path = dir.join(format!("{}.{}", docset_name, extension));
+        if path.is_file() {
+            remove_file(&path).context(IoWrite)?;
+            cargo_cfg.shell().status("Removed", path.display()).context(Cargo)?;
+            removed = true;
+        }
+    }
+    Ok(removed)
+}
+
+/// Remove the docsets generated for `workspace` in `output_dir`, or in the default output
+/// directory of `generate`. Only the docset named `docset_name` is removed if it is set, otherwise
+/// all of those `generate` names after the workspace and its members are, along with the output
+/// directory if nothing else is left in it. The other docsets of the directory are left untouched.
+pub fn clean_docsets(
+    cargo_cfg: &CargoConfig,
+    workspace: &Workspace,
+    output_dir: Option<&Path>,
+    docset_name: Option<&str>
+) -> Result<()> {
+    let dir = docset_output_dir(workspace, output_dir);
+    let mut removed = false;
+    if let Some(docset_name) = docset_name {
+        if docset_name.is_empty() || docset_name == "." || docset_name == ".." || docset_name.contains(is_separator) {
+            return Args { msg: "--docset-name must be a valid file name" }.fail();
+        }
+        removed = remove_docset(cargo_cfg, &dir, docset_name)?;
+    } else if dir.is_dir() {
+        for docset_name in docset_names(workspace)? {
+            removed |= remove_docset(cargo_cfg, &dir, &docset_name)?;
+        }
+        // The output directory may be shared with other files, such as a Dash docset directory.
+        if read_dir(&dir).context(IoRead)?.next().is_none() {
+            remove_dir(&dir).context(IoWrite)?;
+        }
+    }
+    if !removed {
+        cargo_cfg.shell().status("Clean", format!("no docset found in {}", dir.display())).context(Cargo)?;
+    }
+    Ok(())
+}
@@ -2,26 +2,45 @@
 
 use crate::{
     common::{DocsetEntry, EntryType, Package},
-    error::*
+    error::*,
+    html::{add_toc_anchors, fix_links, fix_redirects, linked_pages, member_entries, page_text, reexport_duplicates}
 };
 
 use cargo::{
-    core::{compiler::CompileMode, Workspace},
-    ops::{clean, CleanOptions, doc, CompileFilter, CompileOptions, DocOptions, FilterRule, LibRule, Packages},
+    core::{compiler::{BuildConfig, CompileMode}, Package as CargoPackage, Verbosity, Workspace},
+    ops::{
+        clean, CleanOptions, doc, resolve_ws_precisely, CompileFilter, CompileOptions, DocOptions, FilterRule,
+        LibRule, Packages
+    },
     Config as CargoConfig
 };
-use rusqlite::{params, Connection};
-use snafu::ResultExt;
+use crypto_hash::{hex_digest, Algorithm, Hasher};
+use flate2::{write::GzEncoder, Compression};
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{debug, trace};
+use rayon::prelude::*;
+use rusqlite::{backup::Progress, params, Connection, DatabaseName};
+use snafu::{OptionExt, ResultExt};
+use tar::{Builder as TarBuilder, EntryType as TarEntryType, Header as TarHeader};
+use toml::Value as TomlValue;
 
 use std::{
     borrow::ToOwned,
-    ffi::OsStr,
-    fs::{copy, create_dir_all, read_dir, remove_dir_all, File},
-    io::Write,
-    path::{Path, PathBuf}
+    collections::{BTreeMap, HashSet},
+    env,
+    ffi::{OsStr, OsString},
+    fs::{copy, create_dir_all, hard_link, metadata, read_dir, read_to_string, remove_dir_all, remove_file, rename, File},
+    io::{self, empty, stdout, Read, Write},
+    iter::once,
+    mem::replace,
+    path::{is_separator, Path, PathBuf},
+    process::Command,
+    sync::{mpsc::{sync_channel, SyncSender}, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH}
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GenerateConfig {
     pub package: Package,
     pub no_dependencies: bool,
@@ -32,7 +51,62 @@ pub struct GenerateConfig {
     pub exclude: Vec<String>,
     pub clean: bool,
     pub lib: bool,
-    pub bins: Option<Vec<String>>
+    pub bins: Option<Vec<String>>,
+    pub strict: bool,
+    pub skip_dirs: Vec<String>,
+    pub fast_index: bool,
+    pub check_config: bool,
+    pub fix_redirects: bool,
+    pub primary_only: bool,
+    pub post_hook: Option<String>,
+    pub linked_deps: bool,
+    pub name_separator: String,
+    pub entry_limit: Option<usize>,
+    pub provenance: bool,
+    pub versioned: bool,
+    pub index_members: bool,
+    pub toc: bool,
+    pub online_redirect_url: Option<String>,
+    pub icon: Option<PathBuf>,
+    pub icon_2x: Option<PathBuf>,
+    pub feed: Option<String>,
+    pub zeal: bool,
+    pub output_dir: Option<PathBuf>,
+    pub docset_name: Option<String>,
+    pub target: Option<String>,
+    pub link: bool,
+    pub incremental: bool,
+    pub no_build: bool,
+    pub compress: bool,
+    pub dump_index: Option<PathBuf>,
+    pub dry_run: bool,
+    pub rustdoc_args: Vec<String>,
+    pub docs_rs: bool,
+    pub use_docs_rs_metadata: bool,
+    pub platform_family: Option<String>,
+    pub bundle_identifier: Option<String>,
+    pub open_after: bool,
+    pub split: bool,
+    pub theme: Option<String>,
+    pub jobs: Option<u32>,
+    pub include_paths: Vec<String>,
+    pub exclude_paths: Vec<String>,
+    pub no_source: bool,
+    pub keep_reexports: bool,
+    pub doc_dir: Option<PathBuf>,
+    pub examples: Option<Vec<String>>,
+    pub exclude_types: Vec<EntryType>,
+    pub enable_js: bool,
+    pub keyword: Option<String>,
+    pub fts: bool,
+    pub plist_template: Option<PathBuf>,
+    pub short_aliases: bool,
+    pub only_deps: Vec<String>,
+    pub extern_urls: Vec<(String, String)>,
+    pub timings: bool,
+    pub temp_dir: Option<PathBuf>,
+    pub manifest: bool,
+    pub rustc_args: Vec<String>
 }
 
 impl Default for GenerateConfig {
@@ -43,22 +117,121 @@ impl Default for GenerateConfig {
             doc_private_items: false,
             exclude: Vec::new(),
             features: Vec::new(),
-            no_default_features: true,
+            no_default_features: false,
             all_features: false,
             clean: true,
             lib: false,
-            bins: None
+            bins: None,
+            strict: false,
+            skip_dirs: ROOT_SKIP_DIRS.iter().map(|d| (*d).to_owned()).collect(),
+            fast_index: false,
+            check_config: false,
+            fix_redirects: false,
+            primary_only: false,
+            post_hook: None,
+            linked_deps: false,
+            name_separator: "::".to_owned(),
+            entry_limit: None,
+            provenance: false,
+            versioned: false,
+            index_members: false,
+            toc: false,
+            online_redirect_url: None,
+            icon: None,
+            icon_2x: None,
+            feed: None,
+            zeal: false,
+            output_dir: None,
+            docset_name: None,
+            target: None,
+            link: false,
+            incremental: false,
+            no_build: false,
+            compress: false,
+            dump_index: None,
+            dry_run: false,
+            rustdoc_args: Vec::new(),
+            docs_rs: false,
+            use_docs_rs_metadata: false,
+            platform_family: None,
+            bundle_identifier: None,
+            open_after: false,
+            split: false,
+            theme: None,
+            jobs: None,
+            include_paths: Vec::new(),
+            exclude_paths: Vec::new(),
+            no_source: false,
+            keep_reexports: false,
+            doc_dir: None,
+            examples: None,
+            exclude_types: Vec::new(),
+            enable_js: false,
+            keyword: None,
+            fts: false,
+            plist_template: None,
+            short_aliases: false,
+            only_deps: Vec::new(),
+            extern_urls: Vec::new(),
+            timings: false,
+            temp_dir: None,
+            manifest: false,
+            rustc_args: Vec::new()
         }
     }
 }
 
+/// Pages generated by rustdoc which do not document an item.
+const META_PAGES: &[&str] = &["all.html", "help.html", "settings.html"];
+
+/// Return the type of the items documented in the pages named `<prefix>.<name>.html`, or `None` if
+/// the prefix is unknown.
+fn item_page_type(prefix: &str) -> Option<EntryType> {
+    Some(match prefix {
+        "attr" => EntryType::Attribute,
+        // rustdoc names the constant pages `constant.<name>.html`.
+        "const" | "constant" => EntryType::Constant,
+        "derive" => EntryType::Derive,
+        "enum" => EntryType::Enum,
+        "fn" => EntryType::Function,
+        "static" => EntryType::Static,
+        "macro" => EntryType::Macro,
+        "primitive" => EntryType::Primitive,
+        "trait" => EntryType::Trait,
+        "struct" => EntryType::Struct,
+        "type" => EntryType::Type,
+        "union" => EntryType::Union,
+        _ => return None
+    })
+}
+
+/// Prefixes of the item pages which are deliberately not indexed.
+const UNINDEXED_PAGE_PREFIXES: &[&str] = &["keyword"];
+
+/// Return the prefix of the file named `file_name` if it looks like an item page of an unknown kind,
+/// such as the kinds introduced by newer rustdoc versions.
+fn unrecognized_page_prefix(file_name: &str) -> Option<&str> {
+    let parts = file_name.split('.').collect::<Vec<_>>();
+    match parts[..] {
+        [prefix, _, "html"] if item_page_type(prefix).is_none() && !UNINDEXED_PAGE_PREFIXES.contains(&prefix) => {
+            Some(prefix)
+        }
+        _ => None
+    }
+}
+
 fn parse_docset_entry<P1: AsRef<Path>, P2: AsRef<Path>>(
     module_path: &Option<&str>,
     rustdoc_root_dir: P1,
-    file_path: P2
+    file_path: P2,
+    separator: &str
 ) -> Option<DocsetEntry> {
     if file_path.as_ref().extension() == Some(OsStr::new("html")) {
         let file_name = file_path.as_ref().file_name().unwrap().to_string_lossy();
+        if META_PAGES.contains(&file_name.as_ref()) {
+            debug!("skipping file {}: rustdoc meta page", file_path.as_ref().display());
+            return None;
+        }
         let parts = file_name.split('.').collect::<Vec<_>>();
 
         let file_db_path = file_path
@@ -71,10 +244,11 @@ fn parse_docset_entry<P1: AsRef<Path>, P2: AsRef<Path>>(
                 match parts[0] {
                     "index" => {
                         if let Some(mod_path) = module_path {
-                            if mod_path.contains(':') {
-                                // Module entry
+                            // Crate root pages are located at `<crate>/index.html`.
+                            if file_db_path.components().count() > 2 {
+                                // Module entry, named after the module path without the page name
                                 Some(DocsetEntry::new(
-                                    format!("{}::{}", mod_path, parts[0]),
+                                    mod_path.to_string(),
                                     EntryType::Module,
                                     file_db_path
                                 ))
@@ -87,289 +261,2476 @@ fn parse_docset_entry<P1: AsRef<Path>, P2: AsRef<Path>>(
                                 ))
                             }
                         } else {
+                            debug!("skipping file {}: outside of any crate", file_path.as_ref().display());
                             None
                         }
                     }
-                    _ => None
+                    _ => {
+                        debug!("skipping file {}: not an item page", file_path.as_ref().display());
+                        None
+                    }
                 }
             }
-            3 => match parts[0] {
-                "const" => Some(DocsetEntry::new(
-                    format!("{}::{}", module_path.unwrap().to_string(), parts[1]),
-                    EntryType::Constant,
-                    file_db_path
-                )),
-                "enum" => Some(DocsetEntry::new(
-                    format!("{}::{}", module_path.unwrap().to_string(), parts[1]),
-                    EntryType::Enum,
-                    file_db_path
-                )),
-                "fn" => Some(DocsetEntry::new(
-                    format!("{}::{}", module_path.unwrap().to_string(), parts[1]),
-                    EntryType::Function,
-                    file_db_path
-                )),
-                "macro" => Some(DocsetEntry::new(
-                    format!("{}::{}", module_path.unwrap().to_string(), parts[1]),
-                    EntryType::Macro,
-                    file_db_path
-                )),
-                "trait" => Some(DocsetEntry::new(
-                    format!("{}::{}", module_path.unwrap().to_string(), parts[1]),
-                    EntryType::Trait,
-                    file_db_path
-                )),
-                "struct" => Some(DocsetEntry::new(
-                    format!("{}::{}", module_path.unwrap().to_string(), parts[1]),
-                    EntryType::Struct,
-                    file_db_path
-                )),
-                "type" => Some(DocsetEntry::new(
-                    format!("{}::{}", module_path.unwrap().to_string(), parts[1]),
-                    EntryType::Type,
+            3 => match item_page_type(parts[0]) {
+                Some(ty) => Some(DocsetEntry::new(
+                    format!("{}{}{}", module_path.unwrap(), separator, parts[1]),
+                    ty,
                     file_db_path
                 )),
-                _ => None
+                None => {
+                    debug!("skipping file {}: unrecognized prefix {}", file_path.as_ref().display(), parts[0]);
+                    None
+                }
             },
-            _ => None
+            _ => {
+                debug!("skipping file {}: not an item page", file_path.as_ref().display());
+                None
+            }
         }
     } else {
+        trace!("skipping file {}: not an HTML page", file_path.as_ref().display());
         None
     }
 }
 
+/// Root directories of the rustdoc output which are not indexed by default.
 const ROOT_SKIP_DIRS: &[&str] = &["src", "implementors"];
 
+/// Options controlling how the rustdoc output is indexed.
+struct WalkOptions<'a> {
+    skip_dirs: &'a [String],
+    separator: &'a str,
+    index_members: bool,
+    progress: &'a ProgressBar,
+    /// Number of pages found for each of the unrecognized prefixes.
+    unrecognized: &'a Mutex<BTreeMap<String, usize>>
+}
+
+/// Maximum number of index entries waiting to be written to the index.
+const ENTRY_CHANNEL_CAPACITY: usize = 1024;
+
+/// Walk the rustdoc output and send the index entries to `sink` as they are found. The walk stops
+/// early if the receiving end of the channel is dropped.
 fn recursive_walk(
     root_dir: &Path,
     cur_dir: &Path,
-    module_path: Option<&str>
-) -> Result<Vec<DocsetEntry>> {
+    module_path: Option<&str>,
+    opts: &WalkOptions,
+    sink: &SyncSender<Result<DocsetEntry>>
+) -> Result<()> {
     let dir = read_dir(cur_dir).context(IoRead)?;
-    let mut entries = vec![];
-    let mut subdir_entries = vec![];
+    let mut subdirs = vec![];
 
     for dir_entry in dir {
-        let dir_entry = dir_entry.unwrap();
-        if dir_entry.file_type().unwrap().is_dir() {
+        let dir_entry = dir_entry.context(IoRead)?;
+        if dir_entry.file_type().context(IoRead)?.is_dir() {
             let mut subdir_module_path =
-                module_path.map(|p| format!("{}::", p)).unwrap_or_default();
+                module_path.map(|p| format!("{}{}", p, opts.separator)).unwrap_or_default();
             let dir_name = dir_entry.file_name().to_string_lossy().to_string();
 
             // Ignore some of the root directories which are of no interest to us
-            if !(module_path.is_none() && ROOT_SKIP_DIRS.contains(&dir_name.as_str())) {
+            if !(module_path.is_none() && opts.skip_dirs.contains(&dir_name)) {
                 subdir_module_path.push_str(&dir_name);
-                subdir_entries.push(recursive_walk(
-                    &root_dir,
-                    &dir_entry.path(),
-                    Some(&subdir_module_path)
-                ));
+                subdirs.push((dir_entry.path(), subdir_module_path));
+            } else {
+                debug!("skipping directory {}", dir_entry.path().display());
+            }
+        } else if let Some(entry) = parse_docset_entry(&module_path, root_dir, dir_entry.path(), opts.separator) {
+            let mut page_entries = if opts.index_members && entry.ty.has_members() {
+                member_entries(&entry, &dir_entry.path(), opts.separator)?
+            } else {
+                vec![]
+            };
+            page_entries.push(entry);
+            for page_entry in page_entries {
+                trace!("emitting {} entry {} for {}", page_entry.ty, page_entry.name, page_entry.path.display());
+                if sink.send(Ok(page_entry)).is_err() {
+                    return Ok(());
+                }
+                opts.progress.inc(1);
             }
-        } else if let Some(entry) = parse_docset_entry(&module_path, &root_dir, &dir_entry.path()) {
-            entries.push(entry);
+        } else if let Some(prefix) = unrecognized_page_prefix(&dir_entry.file_name().to_string_lossy()) {
+            *opts.unrecognized.lock().unwrap().entry(prefix.to_owned()).or_default() += 1;
         }
     }
-    for v in subdir_entries {
-        entries.extend(v?);
-    }
-    Ok(entries)
+    // The subdirectories are walked concurrently, the first error aborts the whole walk.
+    subdirs.par_iter().try_for_each(|(subdir, subdir_module_path)| {
+        recursive_walk(root_dir, subdir, Some(subdir_module_path), opts, sink)
+    })
 }
 
-fn generate_sqlite_index<P: AsRef<Path>>(docset_dir: P, entries: Vec<DocsetEntry>) -> Result<()> {
+/// Write the docset entries to the SQLite index, and return the number of entries which were
+/// dropped because another entry with the same (name, type, path) key had already been inserted.
+///
+/// If `in_memory` is true, the index is built in an in-memory database which is then backed up to
+/// the docset directory in one go, avoiding any disk sync during the inserts.
+///
+/// On failure, the partially written index is removed if it was created by this run. An existing
+/// index, updated in incremental mode, is left as it was: the failed transaction is never committed.
+pub(crate) fn generate_sqlite_index<P: AsRef<Path>, I: IntoIterator<Item = Result<DocsetEntry>>>(
+    cargo_cfg: &CargoConfig,
+    docset_dir: P,
+    entries: I,
+    in_memory: bool,
+    incremental: bool
+) -> Result<usize> {
     let mut conn_path = docset_dir.as_ref().to_owned();
     conn_path.push("Contents");
     conn_path.push("Resources");
     conn_path.push("docSet.dsidx");
-    let mut conn = Connection::open(&conn_path).context(Sqlite)?;
-    conn.execute(
-        "CREATE TABLE searchIndex(id INTEGER PRIMARY KEY, name TEXT, type TEXT, path TEXT);
-        CREATE UNIQUE INDEX anchor ON searchIndex (name, type, path);
-        )",
-        params![]
+    let created = !conn_path.exists();
+    let result = write_sqlite_index(&conn_path, entries, in_memory, incremental);
+    if result.is_err() && created {
+        // Including the write-ahead log files, if they were left behind.
+        for suffix in &["", "-wal", "-shm"] {
+            let mut path = conn_path.clone().into_os_string();
+            path.push(suffix);
+            let path = Path::new(&path);
+            if path.exists() {
+                if let Err(e) = remove_file(path) {
+                    // Report the error which made the generation fail, not this one.
+                    let _ = cargo_cfg.shell().warn(format!("cannot remove {}: {}", path.display(), e));
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Write the index entries to the SQLite database at `conn_path`. The entries are first gathered in
+/// a temporary table, then inserted in a stable order, so that the index does not depend on the
+/// order in which the entries are received. Duplicated entries, such as those of items re-exported
+/// under the same path, are only inserted once. In incremental mode, the existing index is updated:
+/// only the new entries are inserted, and the entries which were not received are deleted.
+fn write_sqlite_index<I: IntoIterator<Item = Result<DocsetEntry>>>(
+    conn_path: &Path,
+    entries: I,
+    in_memory: bool,
+    incremental: bool
+) -> Result<usize> {
+    let mut conn = if in_memory {
+        let mut conn = Connection::open_in_memory().context(Sqlite)?;
+        if incremental && conn_path.exists() {
+            conn.restore(DatabaseName::Main, conn_path, None::<fn(Progress)>).context(Sqlite)?;
+        }
+        conn
+    } else {
+        let conn = Connection::open(conn_path).context(Sqlite)?;
+        // Durability does not matter while the index is being built, an interrupted generation is
+        // started over anyway.
+        conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = OFF; PRAGMA cache_size = -65536;")
+            .context(Sqlite)?;
+        conn
+    };
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS searchIndex(id INTEGER PRIMARY KEY, name TEXT, type TEXT, path TEXT);
+        CREATE UNIQUE INDEX IF NOT EXISTS anchor ON searchIndex (name, type, path);"
     )
     .context(Sqlite)?;
     let transaction = conn.transaction().context(Sqlite)?;
+    transaction
+        .execute("CREATE TEMP TABLE receivedEntries(name TEXT, type TEXT, path TEXT)", params![])
+        .context(Sqlite)?;
+    let mut received = 0;
     {
         let mut stmt = transaction
-            .prepare("INSERT INTO searchIndex (name, type, path) VALUES (?1, ?2, ?3)")
+            .prepare("INSERT INTO receivedEntries (name, type, path) VALUES (?1, ?2, ?3)")
             .context(Sqlite)?;
         for entry in entries {
-            stmt.execute(&[
-                entry.name,
-                entry.ty.to_string(),
-                entry.path.to_str().unwrap().to_owned()
-            ])
-            .context(Sqlite)?;
+            let entry = entry?;
+            // The paths are stored as URLs, which cannot represent arbitrary bytes.
+            let path = entry.path.to_str().context(NonUtf8Path { path: &entry.path })?.to_owned();
+            stmt.execute(&[entry.name, entry.ty.to_string(), path]).context(Sqlite)?;
+            received += 1;
         }
     }
+    if incremental {
+        transaction
+            .execute(
+                "DELETE FROM searchIndex WHERE NOT EXISTS (
+                    SELECT 1 FROM receivedEntries AS r
+                    WHERE r.name = searchIndex.name AND r.type = searchIndex.type AND r.path = searchIndex.path
+                )",
+                params![]
+            )
+            .context(Sqlite)?;
+    }
+    transaction
+        .execute(
+            "INSERT OR IGNORE INTO searchIndex (name, type, path)
+            SELECT DISTINCT name, type, path FROM receivedEntries AS r
+            WHERE NOT EXISTS (
+                SELECT 1 FROM searchIndex AS s WHERE s.name = r.name AND s.type = r.type AND s.path = r.path
+            )
+            ORDER BY name, type, path",
+            params![]
+        )
+        .context(Sqlite)?;
+    let indexed: i64 = transaction
+        .query_row("SELECT count(*) FROM searchIndex", params![], |row| row.get(0))
+        .context(Sqlite)?;
+    transaction.execute("DROP TABLE receivedEntries", params![]).context(Sqlite)?;
     transaction.commit().context(Sqlite)?;
+    if in_memory {
+        conn.backup(DatabaseName::Main, conn_path, None).context(Sqlite)?;
+    } else {
+        // The journal mode is persistent, restore the default one so that the docset index is a
+        // single file which can be opened read-only.
+        conn.execute_batch("PRAGMA journal_mode = DELETE;").context(Sqlite)?;
+    }
+    Ok(received - indexed as usize)
+}
+
+/// Return the entry named after the last segment of the path of `entry`, or `None` if the entry is
+/// not named after a path, such as the package entries. The trait implementations and the
+/// sections are named after their title, which may contain paths, and have no alias.
+fn short_alias(entry: &DocsetEntry, separator: &str) -> Option<DocsetEntry> {
+    if !entry.ty.is_primary() && !entry.ty.is_associated_item() {
+        return None;
+    }
+    let start = entry.name.rfind(separator)? + separator.len();
+    Some(DocsetEntry::new(entry.name[start..].to_owned(), entry.ty.clone(), entry.path.clone()))
+}
+
+/// Follow each of the `entries` with its short alias if `enabled` is true, so that searching for
+/// the bare name of an item ranks it first.
+fn with_short_aliases<'a, I: IntoIterator<Item = Result<DocsetEntry>> + 'a>(
+    entries: I,
+    enabled: bool,
+    separator: &'a str
+) -> impl Iterator<Item = Result<DocsetEntry>> + 'a {
+    entries.into_iter().flat_map(move |entry| {
+        let alias = match &entry {
+            Ok(entry) if enabled => short_alias(entry, separator).map(Ok),
+            _ => None
+        };
+        once(entry).chain(alias)
+    })
+}
+
+/// Write a full-text search index of the pages of the index entries to the `searchContent` FTS5
+/// table of the docset index, with the text of the pages located under `docs_root`. The table is
+/// rebuilt from scratch, in incremental mode too.
+fn write_fts_index(docset_dir: &Path, docs_root: &Path, progress: &ProgressBar) -> Result<()> {
+    let conn_path = docset_dir.join("Contents").join("Resources").join("docSet.dsidx");
+    let mut conn = Connection::open(conn_path).context(Sqlite)?;
+    // The member entries point at anchors of the pages which are already indexed.
+    let pages = {
+        let mut stmt = conn
+            .prepare("SELECT DISTINCT path FROM searchIndex WHERE instr(path, '#') = 0 ORDER BY path")
+            .context(Sqlite)?;
+        let pages = stmt.query_map(params![], |row| row.get::<_, String>(0)).context(Sqlite)?;
+        pages.collect::<std::result::Result<Vec<_>, _>>().context(Sqlite)?
+    };
+    progress.set_length(pages.len() as u64);
+    let texts = pages
+        .par_iter()
+        .map(|page| {
+            let text = page_text(&docs_root.join(page));
+            progress.inc(1);
+            text
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let transaction = conn.transaction().context(Sqlite)?;
+    transaction
+        .execute_batch(
+            "DROP TABLE IF EXISTS searchContent;
+            CREATE VIRTUAL TABLE searchContent USING fts5(path UNINDEXED, title, body);"
+        )
+        .context(Sqlite)?;
+    {
+        let mut stmt = transaction
+            .prepare("INSERT INTO searchContent (path, title, body) VALUES (?1, ?2, ?3)")
+            .context(Sqlite)?;
+        for (page, (title, body)) in pages.iter().zip(texts) {
+            stmt.execute(params![page, title, body]).context(Sqlite)?;
+        }
+    }
+    transaction.commit().context(Sqlite)
+}
+
+/// Write the index entries as JSON to the file at `path`, or to stdout if `path` is `-`.
+fn dump_index(entries: &[DocsetEntry], path: &Path) -> Result<()> {
+    if path == Path::new("-") {
+        let stdout = stdout();
+        serde_json::to_writer_pretty(stdout.lock(), entries).context(DumpIndex)?;
+        println!();
+    } else {
+        let dump_file = File::create(path).context(IoWrite)?;
+        serde_json::to_writer_pretty(dump_file, entries).context(DumpIndex)?;
+    }
+    Ok(())
+}
+
+/// Number of index entries of each type.
+pub type EntryTypeCounts = BTreeMap<EntryType, usize>;
+
+/// Docset written by a generation.
+#[derive(Debug, Clone)]
+pub struct GeneratedDocset {
+    /// Path of the `.docset` directory.
+    pub path: PathBuf,
+    /// Number of entries of each type found for the docset index, before the conflicting entries are
+    /// dropped, and without the short aliases.
+    pub entry_counts: EntryTypeCounts
+}
+
+/// Count the entries of each type.
+fn count_entry_types<'a, I: IntoIterator<Item = &'a DocsetEntry>>(entries: I, counts: &mut EntryTypeCounts) {
+    for entry in entries {
+        *counts.entry(entry.ty.clone()).or_insert(0) += 1;
+    }
+}
+
+/// Summarize the number of entries of each type, e.g. `3 structs, 1 trait`.
+fn entry_summary(counts: &EntryTypeCounts) -> String {
+    if counts.is_empty() {
+        return "no entries".to_owned();
+    }
+    counts
+        .iter()
+        .map(|(ty, count)| (ty.description(), count))
+        .map(|(description, count)| match count {
+            1 => format!("1 {}", description),
+            _ if description.ends_with('s') => format!("{} {}es", count, description),
+            _ => format!("{} {}s", count, description)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Report what would be generated by a dry run.
+fn report_dry_run(
+    cargo_cfg: &CargoConfig,
+    entries: &[DocsetEntry],
+    docset_name: &str,
+    docset_root_dir: &Path
+) -> Result<()> {
+    let mut shell = cargo_cfg.shell();
+    shell.status("Docset", docset_name).context(Cargo)?;
+    shell.status("Output", docset_root_dir.display()).context(Cargo)?;
+    let mut type_counts = EntryTypeCounts::new();
+    count_entry_types(entries, &mut type_counts);
+    shell.status("Entries", entry_summary(&type_counts)).context(Cargo)?;
+    Ok(())
+}
+
+/// Return the number of files located under `dir`, recursively, except those in the `skipped_dirs`
+/// subdirectories of `dir`, which are not copied.
+fn count_files(dir: &Path, skipped_dirs: &[&str]) -> Result<u64> {
+    let mut count = 0;
+    for entry in read_dir(dir).context(IoRead)? {
+        let entry = entry.context(IoRead)?.path();
+        if entry.is_dir() {
+            if !skipped_dirs.iter().any(|d| entry.file_name() == Some(OsStr::new(d))) {
+                count += count_files(&entry, &[])?;
+            }
+        } else if entry.is_file() {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Create a progress bar for the `len` steps of a generation phase, or a spinner counting the steps
+/// if their number is unknown. The progress is only drawn when stderr is a terminal, and never in
+/// quiet mode. Like the cargo status, the prefix is only colored if the shell supports colors.
+pub(crate) fn progress_bar(cargo_cfg: &CargoConfig, phase: &'static str, len: Option<u64>) -> ProgressBar {
+    let shell = cargo_cfg.shell();
+    if shell.verbosity() == Verbosity::Quiet {
+        return ProgressBar::hidden();
+    }
+    let prefix = if shell.supports_color() { "{prefix:>12.cyan.bold}" } else { "{prefix:>12}" };
+    let (progress, template) = match len {
+        Some(len) => (ProgressBar::new(len), format!("{} [{{bar:40}}] {{pos}}/{{len}} files", prefix)),
+        None => (ProgressBar::new_spinner(), format!("{} {{spinner}} {{pos}} entries", prefix))
+    };
+    progress.set_style(ProgressStyle::with_template(&template).unwrap().progress_chars("=> "));
+    progress.set_prefix(phase);
+    progress
+}
+
+/// Error code of the hard link failures due to the source and destination being on different
+/// devices.
+#[cfg(unix)]
+const CROSS_DEVICE_LINK_ERROR: i32 = 18; // EXDEV
+#[cfg(windows)]
+const CROSS_DEVICE_LINK_ERROR: i32 = 17; // ERROR_NOT_SAME_DEVICE
+
+/// Options controlling how the rustdoc output is copied to the docset.
+pub(crate) struct CopyOptions<'a> {
+    pub(crate) link: bool,
+    pub(crate) incremental: bool,
+    pub(crate) progress: &'a ProgressBar
+}
+
+/// Return whether the file `dst` is a copy of the current version of `src`, judging by their size
+/// and modification time.
+fn is_up_to_date(src: &Path, dst: &Path) -> Result<bool> {
+    let src_metadata = metadata(src).context(IoRead)?;
+    let dst_metadata = metadata(dst).context(IoRead)?;
+    let src_mtime = src_metadata.modified().context(IoRead)?;
+    let dst_mtime = dst_metadata.modified().context(IoRead)?;
+    Ok(src_metadata.len() == dst_metadata.len() && src_mtime <= dst_mtime)
+}
+
+/// Copy the file `src` to `dst`, or hard link it if requested and both are on the same device. In
+/// incremental mode, up to date files are left untouched.
+fn copy_file<Ps: AsRef<Path>, Pd: AsRef<Path>>(src: Ps, dst: Pd, opts: &CopyOptions) -> Result<()> {
+    let (src, dst) = (src.as_ref(), dst.as_ref());
+    opts.progress.inc(1);
+    if opts.incremental && dst.is_file() {
+        if is_up_to_date(src, dst)? {
+            return Ok(());
+        }
+        // The destination may be a hard link to the source.
+        remove_file(dst).context(IoWrite)?;
+    }
+    if opts.link {
+        match hard_link(src, dst) {
+            Ok(()) => return Ok(()),
+            Err(e) if e.raw_os_error() == Some(CROSS_DEVICE_LINK_ERROR) => {}
+            Err(e) => return Err(e).context(IoWrite)
+        }
+    }
+    copy(src, dst).context(IoWrite)?;
     Ok(())
 }
 
-fn copy_dir_recursive<Ps: AsRef<Path>, Pd: AsRef<Path>>(src: Ps, dst: Pd) -> Result<()> {
+/// Remove the entries of the `dst` directory which do not exist in the `src` directory anymore.
+fn remove_stale_entries(src: &Path, dst: &Path) -> Result<()> {
+    for dir_entry in read_dir(dst).context(IoRead)? {
+        let dir_entry = dir_entry.context(IoRead)?;
+        let entry = dir_entry.path();
+        if !src.join(dir_entry.file_name()).exists() {
+            if entry.is_dir() {
+                remove_dir_all(&entry).context(IoWrite)?;
+            } else {
+                remove_file(&entry).context(IoWrite)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn copy_dir_recursive<Ps: AsRef<Path>, Pd: AsRef<Path>>(src: Ps, dst: Pd, opts: &CopyOptions) -> Result<()> {
     create_dir_all(&dst).context(IoWrite)?;
-    for entry in read_dir(&src).context(IoRead)? {
-        let entry = entry.context(IoWrite)?.path();
+    if opts.incremental {
+        remove_stale_entries(src.as_ref(), dst.as_ref())?;
+    }
+    // The destination directory exists at this point, its entries are copied concurrently, and the
+    // first error aborts the whole copy.
+    let dst = dst.as_ref();
+    sorted_dir_paths(src.as_ref())?.into_par_iter().try_for_each(|entry| {
+        let dst_entry = dst.join(entry.file_name().unwrap());
+        trace!("copying {} to {}", entry.display(), dst_entry.display());
         if entry.is_dir() {
-            let mut dst_dir = dst.as_ref().to_owned();
-            dst_dir.push(entry.strip_prefix(&src).unwrap());
-            copy_dir_recursive(entry, dst_dir)?;
+            copy_dir_recursive(entry, dst_entry, opts)
         } else if entry.is_file() {
-            let mut dst_file = dst.as_ref().to_owned();
-            dst_file.push(entry.file_name().unwrap());
-            copy(entry, dst_file).context(IoWrite)?;
+            copy_file(entry, dst_entry, opts)
+        } else {
+            Ok(())
+        }
+    })
+}
+
+/// Return the paths of the entries of `dir`, sorted so that the walks do not depend on the order
+/// of the file system.
+fn sorted_dir_paths(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = read_dir(dir)
+        .context(IoRead)?
+        .map(|dir_entry| dir_entry.map(|dir_entry| dir_entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()
+        .context(IoRead)?;
+    paths.sort();
+    Ok(paths)
+}
+
+/// Root directory of the rustdoc output containing the source pages.
+const SOURCE_DIR: &str = "src";
+
+/// Copy the rustdoc output to the docset, except for the root directories in `skipped_dirs`. With
+/// `linked_deps`, only the pages of the dependency crates which are linked from the documented
+/// crates are kept. Root directories which are not crate documentation directories (e.g. static
+/// files, sources) are copied as-is.
+fn copy_docs<Ps: AsRef<Path>, Pd: AsRef<Path>>(
+    src: Ps,
+    dst: Pd,
+    linked_deps: Option<(&[String], &HashSet<PathBuf>)>,
+    skipped_dirs: &[&str],
+    opts: &CopyOptions
+) -> Result<()> {
+    create_dir_all(&dst).context(IoWrite)?;
+    if opts.incremental {
+        remove_stale_entries(src.as_ref(), dst.as_ref())?;
+    }
+    let is_skipped = |name: &OsStr| skipped_dirs.iter().any(|d| OsStr::new(d) == name);
+    for entry in sorted_dir_paths(src.as_ref())? {
+        let name = entry.file_name().unwrap().to_owned();
+        let dst_entry = dst.as_ref().join(&name);
+        let is_dependency_dir = match linked_deps {
+            Some((crate_dirs, _)) => {
+                entry.is_dir()
+                    && entry.join("index.html").is_file()
+                    && !crate_dirs.iter().any(|d| OsStr::new(d) == name)
+            }
+            None => false
+        };
+        if is_dependency_dir || (entry.is_dir() && is_skipped(&name)) {
+            // The linked pages of the dependencies may have changed, they are copied again below.
+            if opts.incremental && dst_entry.is_dir() {
+                remove_dir_all(&dst_entry).context(IoWrite)?;
+            }
+        } else if entry.is_dir() {
+            copy_dir_recursive(&entry, &dst_entry, opts)?;
+        } else if entry.is_file() {
+            copy_file(&entry, &dst_entry, opts)?;
+        }
+    }
+    if let Some((_, linked)) = linked_deps {
+        for page in linked {
+            if page.components().next().map_or(false, |c| is_skipped(c.as_os_str())) {
+                continue;
+            }
+            let src_page = src.as_ref().join(page);
+            if src_page.is_file() {
+                let dst_page = dst.as_ref().join(page);
+                create_dir_all(dst_page.parent().unwrap()).context(IoWrite)?;
+                copy_file(&src_page, &dst_page, opts)?;
+            }
         }
     }
     Ok(())
 }
 
-fn write_metadata<P: AsRef<Path>>(docset_root_dir: P, package_name: &str) -> Result<()> {
-    let mut info_plist_path = docset_root_dir.as_ref().to_owned();
-    info_plist_path.push("Contents");
-    info_plist_path.push("Info.plist");
+/// Move the docset built at `staged_dir` to `docset_root_dir`, replacing the previous docset. The
+/// docset is first moved next to its destination, by renaming it if possible or by copying it
+/// otherwise, such as across file systems. The previous docset is only replaced once the new one is
+/// complete, so that a failure leaves it untouched.
+fn move_docset(staged_dir: &Path, docset_root_dir: &Path) -> Result<()> {
+    create_dir_all(docset_root_dir.parent().unwrap()).context(IoWrite)?;
+    let partial_dir = docset_root_dir.with_extension("docset.partial");
+    if partial_dir.exists() {
+        remove_dir_all(&partial_dir).context(IoWrite)?;
+    }
+    if rename(staged_dir, &partial_dir).is_err() {
+        let copy_opts = CopyOptions {
+            link: false,
+            incremental: false,
+            progress: &ProgressBar::hidden()
+        };
+        if let Err(e) = copy_dir_recursive(staged_dir, &partial_dir, &copy_opts) {
+            // The partial copy is useless, the staged docset is kept.
+            let _ = remove_dir_all(&partial_dir);
+            return Err(e);
+        }
+        remove_dir_all(staged_dir).context(IoWrite)?;
+    }
+    if docset_root_dir.exists() {
+        remove_dir_all(docset_root_dir).context(IoWrite)?;
+    }
+    rename(&partial_dir, docset_root_dir).context(IoWrite)
+}
 
-    let mut info_file = File::create(info_plist_path).context(IoWrite)?;
-    write!(info_file,
-        "\
-        <?xml version=\"1.0\" encoding=\"UTF-8\"?>
+/// Contents of the docset metadata files.
+pub(crate) struct DocsetMetadata<'a> {
+    pub(crate) docset_name: &'a str,
+    pub(crate) bundle_identifier: &'a str,
+    pub(crate) index_file_path: &'a str,
+    pub(crate) platform_family: &'a str,
+    pub(crate) keyword: &'a str,
+    pub(crate) version: Option<&'a str>,
+    pub(crate) toc: bool,
+    pub(crate) fallback_url: Option<&'a str>,
+    pub(crate) zeal: bool,
+    pub(crate) javascript: bool,
+    pub(crate) template: Option<&'a str>
+}
+
+/// Default template of the Info.plist file. The `{{optional_keys}}` placeholder is replaced with the
+/// keys enabled by the settings, such as the table of contents.
+const INFO_PLIST_TEMPLATE: &str = "\
+<?xml version=\"1.0\" encoding=\"UTF-8\"?>
         <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">
         <plist version=\"1.0\">
         <dict>
             <key>CFBundleIdentifier</key>
-                <string>{}</string>
+                <string>{{identifier}}</string>
             <key>CFBundleName</key>
-                <string>{}</string>
+                <string>{{name}}</string>
             <key>dashIndexFilePath</key>
-                <string>{}/index.html</string>
+                <string>{{index_path}}</string>
             <key>DocSetPlatformFamily</key>
-                <string>{}</string>
+                <string>{{platform_family}}</string>
+            <key>DashDocSetPluginKeyword</key>
+                <string>{{keyword}}</string>
             <key>isDashDocset</key>
-                <true/>
+                <true/>{{optional_keys}}
         </dict>
-        </plist>",
-         package_name, package_name, package_name, package_name).context(IoWrite)?;
+        </plist>";
+
+/// Placeholder of an Info.plist template replaced with the keys enabled by the settings.
+const OPTIONAL_KEYS_PLACEHOLDER: &str = "{{optional_keys}}";
+
+/// Placeholders which an Info.plist template must contain for the docset to be usable.
+const REQUIRED_PLIST_PLACEHOLDERS: &[&str] = &["{{identifier}}", "{{name}}", "{{index_path}}"];
+
+/// Read the custom Info.plist template at `path`, checking that it contains the required
+/// placeholders.
+fn read_plist_template(path: &Path) -> Result<String> {
+    let template = read_to_string(path).context(IoRead)?;
+    if let Some(placeholder) = REQUIRED_PLIST_PLACEHOLDERS.iter().find(|p| !template.contains(*p)) {
+        return PlistTemplate { path, placeholder: *placeholder }.fail();
+    }
+    Ok(template)
+}
+
+pub(crate) fn write_metadata<P: AsRef<Path>>(docset_root_dir: P, metadata: &DocsetMetadata) -> Result<()> {
+    let mut info_plist_path = docset_root_dir.as_ref().to_owned();
+    info_plist_path.push("Contents");
+    info_plist_path.push("Info.plist");
+
+    let mut optional_keys = String::new();
+    if metadata.toc {
+        optional_keys.push_str("
+            <key>DashDocSetFamily</key>
+                <string>dashtoc</string>");
+    }
+    // Dash disables JavaScript in the docset pages by default, which breaks the rustdoc search.
+    if metadata.javascript {
+        optional_keys.push_str("
+            <key>isJavaScriptEnabled</key>
+                <true/>");
+    }
+    if let Some(fallback_url) = metadata.fallback_url {
+        optional_keys.push_str(&format!("
+            <key>DashDocSetFallbackURL</key>
+                <string>{}</string>", xml_escape(fallback_url)));
+    }
+
+    let index_path = if metadata.zeal {
+        zeal_index_file_path(metadata.index_file_path)
+    } else {
+        metadata.index_file_path.to_owned()
+    };
+    let info_plist = metadata
+        .template
+        .unwrap_or(INFO_PLIST_TEMPLATE)
+        .replace("{{identifier}}", &xml_escape(metadata.bundle_identifier))
+        .replace("{{name}}", &xml_escape(metadata.docset_name))
+        .replace("{{index_path}}", &xml_escape(&index_path))
+        .replace("{{platform_family}}", &xml_escape(metadata.platform_family))
+        .replace("{{keyword}}", &xml_escape(metadata.keyword))
+        .replace(OPTIONAL_KEYS_PLACEHOLDER, &optional_keys);
+    let mut info_file = File::create(info_plist_path).context(IoWrite)?;
+    info_file.write_all(info_plist.as_bytes()).context(IoWrite)?;
+
+    if metadata.zeal {
+        write_zeal_metadata(docset_root_dir, metadata)?;
+    }
     Ok(())
 }
 
-pub fn generate(cargo_cfg: &CargoConfig, workspace: &Workspace, cfg: GenerateConfig) -> Result<()> {
-    // Step 1: generate rustdoc
-    // Figure out for which crate to build the doc and invoke cargo doc.
-    // If no crate is specified, run cargo doc for the current crate/workspace.
-    let mut compile_opts = CompileOptions::new(
-        &cargo_cfg,
-        CompileMode::Doc {
-            deps: !cfg.no_dependencies
+/// Write the `meta.json` file Zeal reads alongside the Info.plist.
+/// Return `index_file_path` in the form Zeal resolves: a plain relative file path, with forward
+/// slashes and without any anchor or query, which Zeal would look up as part of the file name.
+fn zeal_index_file_path(index_file_path: &str) -> String {
+    let path_len = index_file_path.find(|c| c == '#' || c == '?').unwrap_or(index_file_path.len());
+    let path = index_file_path[..path_len].replace('\\', "/");
+    let path = path.trim_start_matches(|c| c == '.' || c == '/');
+    if path.is_empty() {
+        "index.html".to_owned()
+    } else {
+        path.to_owned()
+    }
+}
+
+fn write_zeal_metadata<P: AsRef<Path>>(docset_root_dir: P, metadata: &DocsetMetadata) -> Result<()> {
+    let mut meta_json_path = docset_root_dir.as_ref().to_owned();
+    meta_json_path.push("meta.json");
+    let mut meta_file = File::create(meta_json_path).context(IoWrite)?;
+    write!(meta_file,
+        "\
+        {{
+            \"name\": {},
+            \"title\": {},
+            \"version\": {}
+        }}
+        ",
+        json_string(metadata.bundle_identifier),
+        json_string(metadata.docset_name),
+        json_string(metadata.version.unwrap_or_default())).context(IoWrite)?;
+    Ok(())
+}
+
+/// Escape a string for inclusion in an XML document.
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Return the base URL Dash should prepend to the page paths, relative to the `Documents`
+/// directory, of the pages which are missing from the docset. The URL of the documentation of the
+/// crate itself, such as `https://docs.rs/mycrate/1.2.3/mycrate/`, is also accepted, in which case
+/// the crate directory is stripped to mirror the local layout. The pages of versioned docsets are
+/// looked up in the documentation of their `version`, which replaces a trailing `latest` segment.
+fn fallback_url(url: &str, crate_dir: &str, version: Option<&str>) -> String {
+    let mut url = url.trim_end_matches('/').to_owned();
+    if url.ends_with(&format!("/{}", crate_dir)) {
+        url.truncate(url.len() - crate_dir.len() - 1);
+    }
+    if let Some(version) = version {
+        if url.ends_with("/latest") {
+            url.truncate(url.len() - "latest".len());
+            url.push_str(version);
+        } else if !url.ends_with(&format!("/{}", version)) {
+            url.push('/');
+            url.push_str(version);
         }
-    ).context(CargoDoc)?;
-    compile_opts.all_features = cfg.all_features;
-    compile_opts.no_default_features = cfg.no_default_features;
-    compile_opts.features = cfg.features;
-    if cfg.lib || cfg.bins.is_some() {
-        let bins_filter_rule =
-            if let Some(bins) = cfg.bins {
-                if bins.is_empty() {
-                    FilterRule::All
-                }
-                else {
-                    FilterRule::Just(bins)
-                }
-            }
-            else { FilterRule::Just(vec![]) };
-        compile_opts.filter = CompileFilter::Only {
-            all_targets: false,
-            lib: if cfg.lib { LibRule::True } else { LibRule::Default },
-            bins: bins_filter_rule,
-            examples: FilterRule::Just(vec![]),
-            tests: FilterRule::Just(vec![]),
-            benches: FilterRule::Just(vec![]),
+    }
+    url.push('/');
+    url
+}
+
+/// Append the contents of `dir` to `archive` under `archive_dir`, in a reproducible way: the
+/// entries are sorted and their ownership, permissions and modification time are normalized.
+fn append_dir_reproducible<W: Write>(archive: &mut TarBuilder<W>, dir: &Path, archive_dir: &Path) -> Result<()> {
+    for path in sorted_dir_paths(dir)? {
+        let archive_path = archive_dir.join(path.file_name().unwrap());
+        let mut header = TarHeader::new_gnu();
+        header.set_mtime(0);
+        header.set_uid(0);
+        header.set_gid(0);
+        if path.is_dir() {
+            header.set_entry_type(TarEntryType::Directory);
+            header.set_mode(0o755);
+            header.set_size(0);
+            archive.append_data(&mut header, &archive_path, empty()).context(IoWrite)?;
+            append_dir_reproducible(archive, &path, &archive_path)?;
+        } else {
+            let file = File::open(&path).context(IoRead)?;
+            header.set_mode(0o644);
+            header.set_size(file.metadata().context(IoRead)?.len());
+            archive.append_data(&mut header, &archive_path, file).context(IoWrite)?;
         }
     }
-    if cfg.doc_private_items {
-        compile_opts.local_rustdoc_args = Some(vec!["--document-private-items".to_owned()]);
+    Ok(())
+}
+
+/// Write a reproducible `<name>.tgz` archive of the docset next to it.
+fn write_docset_archive(docset_root_dir: &Path, docset_name: &str) -> Result<()> {
+    let archive_path = docset_root_dir.with_file_name(format!("{}.tgz", docset_name));
+    let archive_file = File::create(archive_path).context(IoWrite)?;
+    let mut archive = TarBuilder::new(GzEncoder::new(archive_file, Compression::default()));
+    let docset_dir_name = Path::new(docset_root_dir.file_name().unwrap());
+    let mut header = TarHeader::new_gnu();
+    header.set_entry_type(TarEntryType::Directory);
+    header.set_mtime(0);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_mode(0o755);
+    header.set_size(0);
+    archive.append_data(&mut header, docset_dir_name, empty()).context(IoWrite)?;
+    append_dir_reproducible(&mut archive, docset_root_dir, docset_dir_name)?;
+    archive.into_inner().context(IoWrite)?.finish().context(IoWrite)?;
+    Ok(())
+}
+
+/// Write the `<name>.xml` Dash feed file next to the docset, pointing at the docset archive under
+/// `base_url`.
+fn write_feed(docset_root_dir: &Path, docset_name: &str, version: &str, base_url: &str) -> Result<()> {
+    let feed_path = docset_root_dir.with_file_name(format!("{}.xml", docset_name));
+    let archive_name = format!("{}.tgz", docset_name);
+    let mut feed_file = File::create(feed_path).context(IoWrite)?;
+    write!(feed_file,
+        "\
+        <entry>
+            <version>{}</version>
+            <url>{}/{}</url>
+        </entry>
+        ",
+        xml_escape(version),
+        xml_escape(base_url.trim_end_matches('/')),
+        xml_escape(&archive_name.replace(' ', "%20"))).context(IoWrite)?;
+    Ok(())
+}
+
+/// Signature starting every PNG file.
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// File name of the icon picked up from the workspace root when no icon is specified.
+const DEFAULT_ICON: &str = "docset-icon.png";
+/// File name of the high resolution icon picked up from the workspace root when no icon is
+/// specified.
+const DEFAULT_ICON_2X: &str = "docset-icon@2x.png";
+
+/// Check that the icon at `path` exists and is a PNG file.
+fn check_icon(path: &Path) -> Result<()> {
+    if !path.is_file() {
+        return Icon { path, msg: "file not found" }.fail();
     }
-    let root_package_name = match &cfg.package {
-        Package::All => {
-            if cfg.exclude.is_empty() {
-                compile_opts.spec = Packages::All;
-            } else {
-                compile_opts.spec = Packages::OptOut(cfg.exclude.clone());
-            }
-            workspace
-                .root()
-                .file_name()
-                .unwrap()
-                .to_string_lossy()
-                .to_string()
-        }
-        Package::Current => {
-            compile_opts.spec = Packages::Default;
-            workspace
-                .current()
-                .context(Cargo)?
-                .name()
-                .as_str()
-                .to_owned()
-        }
-        Package::Single(name) => {
-            compile_opts.spec = Packages::Packages(vec![name.clone()]);
-            name.to_owned()
-        }
-        Package::List(packages) => {
-            compile_opts.spec = Packages::Packages(packages.clone());
-            workspace
-                .root()
-                .file_name()
-                .unwrap()
-                .to_string_lossy()
-                .to_string()
-        }
-    };
-    if cfg.package != Package::All && !cfg.exclude.is_empty() {
-        return Args {
-            msg: "--exclude must be used with --all"
+    let mut signature = [0; 8];
+    let mut icon_file = File::open(path).context(IoRead)?;
+    if icon_file.read_exact(&mut signature).is_err() || signature != PNG_SIGNATURE {
+        return Icon { path, msg: "not a PNG file" }.fail();
+    }
+    Ok(())
+}
+
+/// Return the docset icons, `(icon, icon_2x)`, checking them if explicitly specified, or picking
+/// them from `default_dir` otherwise.
+fn docset_icons(
+    default_dir: Option<&Path>,
+    icon: Option<PathBuf>,
+    icon_2x: Option<PathBuf>
+) -> Result<(Option<PathBuf>, Option<PathBuf>)> {
+    if icon.is_none() && icon_2x.is_none() {
+        let default_icon = |name| default_dir.map(|dir| dir.join(name)).filter(|path| check_icon(path).is_ok());
+        return Ok((default_icon(DEFAULT_ICON), default_icon(DEFAULT_ICON_2X)));
+    }
+    for icon in icon.iter().chain(icon_2x.iter()) {
+        check_icon(icon)?;
+    }
+    Ok((icon, icon_2x))
+}
+
+/// Return the package the docset is named after, if any.
+fn root_package<'a>(workspace: &'a Workspace, package: &Package) -> Result<Option<&'a CargoPackage>> {
+    Ok(match package {
+        Package::Current => Some(workspace.current().context(Cargo)?),
+        Package::Single(name) => workspace.members().find(|p| p.name().as_str() == name),
+        Package::All | Package::List(_) => workspace.current_opt()
+    })
+}
+
+/// Return the version of the package the docset is named after.
+fn docset_version(workspace: &Workspace, package: &Package) -> Result<String> {
+    match root_package(workspace, package)? {
+        Some(root_package) => Ok(root_package.version().to_string()),
+        None => Args {
+            msg: "--versioned and --feed require a single package, or a workspace with a root package"
         }
-        .fail();
+        .fail()
     }
-    let mut docset_root_dir = PathBuf::new();
-    docset_root_dir.push(workspace.root());
-    docset_root_dir.push("target");
-    let mut rustdoc_root_dir = docset_root_dir.clone();
-    rustdoc_root_dir.push("doc");
-    docset_root_dir.push("docset");
-    docset_root_dir.push(format!("{}.docset", root_package_name));
+}
 
-    if cfg.clean {
-        let clean_options = CleanOptions { config: &cargo_cfg, spec: vec![], target: None, release: false, doc: true };
-        clean(&workspace, &clean_options).context(CargoClean)?;
+/// Return the strings of the array `value`, if it is one.
+fn string_array(value: Option<&TomlValue>) -> Vec<String> {
+    value
+        .and_then(TomlValue::as_array)
+        .map(|values| values.iter().filter_map(TomlValue::as_str).map(ToOwned::to_owned).collect())
+        .unwrap_or_default()
+}
+
+/// Apply the `[package.metadata.docs.rs]` table of a manifest to `cfg`. The features and the
+/// rustdoc arguments set on the command line take precedence, and the rustc arguments are passed
+/// through `RUSTFLAGS` during the build, as docs.rs does.
+fn apply_docs_rs_metadata(cfg: &mut GenerateConfig, docs_rs: &TomlValue) {
+    if cfg.features.is_empty() {
+        cfg.features = string_array(docs_rs.get("features"));
     }
-    // Good to go, generate the documentation.
-    let doc_cfg = DocOptions {
-        open_result: false,
-        compile_opts
-    };
-    doc(&workspace, &doc_cfg).context(CargoDoc)?;
+    cfg.all_features |= docs_rs.get("all-features").and_then(TomlValue::as_bool).unwrap_or(false);
+    cfg.no_default_features |= docs_rs.get("no-default-features").and_then(TomlValue::as_bool).unwrap_or(false);
+    let mut rustdoc_args = string_array(docs_rs.get("rustdoc-args"));
+    rustdoc_args.append(&mut cfg.rustdoc_args);
+    cfg.rustdoc_args = rustdoc_args;
 
-    // Step 2: iterate over all the html files in the doc directory and parse the filenames
-    let entries = recursive_walk(&rustdoc_root_dir, &rustdoc_root_dir, None)?;
+    let mut rustc_args = string_array(docs_rs.get("rustc-args"));
+    rustc_args.append(&mut cfg.rustc_args);
+    cfg.rustc_args = rustc_args;
+}
 
-    // Step 3: generate the SQLite database
-    // At this point, we need to start writing into the output docset directory, so create the
-    // hirerarchy, and clean it first if it already exists.
-    if docset_root_dir.exists() {
-        remove_dir_all(&docset_root_dir).context(IoWrite)?;
+/// `RUSTFLAGS` extended with extra rustc arguments, until it is dropped and the original value is
+/// restored. This keeps the arguments from piling up over successive generations.
+struct ExtendedRustflags {
+    original: Option<Option<OsString>>
+}
+
+impl ExtendedRustflags {
+    fn new(rustc_args: &[String]) -> ExtendedRustflags {
+        if rustc_args.is_empty() {
+            return ExtendedRustflags { original: None };
+        }
+        let original = env::var_os("RUSTFLAGS");
+        let mut rustflags = original.clone().unwrap_or_default();
+        for arg in rustc_args {
+            if !rustflags.is_empty() {
+                rustflags.push(" ");
+            }
+            rustflags.push(arg);
+        }
+        env::set_var("RUSTFLAGS", rustflags);
+        ExtendedRustflags { original: Some(original) }
     }
-    let mut docset_hierarchy = docset_root_dir.clone();
-    docset_hierarchy.push("Contents");
-    docset_hierarchy.push("Resources");
-    create_dir_all(&docset_hierarchy).context(IoWrite)?;
-    generate_sqlite_index(&docset_root_dir, entries)?;
+}
 
-    // Step 4: Copy the rustdoc to the docset directory
-    docset_hierarchy.push("Documents");
-    copy_dir_recursive(&rustdoc_root_dir, &docset_hierarchy)?;
+impl Drop for ExtendedRustflags {
+    fn drop(&mut self) {
+        match self.original.take() {
+            Some(Some(original)) => env::set_var("RUSTFLAGS", original),
+            Some(None) => env::remove_var("RUSTFLAGS"),
+            None => {}
+        }
+    }
+}
 
-    // Step 5: add the required metadata
-    write_metadata(&docset_root_dir, &root_package_name)?;
+/// Apply the `[package.metadata.docset]` table of the manifest of `package` to `cfg`. The settings
+/// made on the command line take precedence over the table.
+fn apply_docset_metadata(cfg: &mut GenerateConfig, package: &CargoPackage, docset: &TomlValue) {
+    let string = |key| docset.get(key).and_then(TomlValue::as_str).map(ToOwned::to_owned);
+    if cfg.docset_name.is_none() {
+        cfg.docset_name = string("name");
+    }
+    // The icon paths are relative to the package root.
+    if cfg.icon.is_none() && cfg.icon_2x.is_none() {
+        cfg.icon = string("icon").map(|icon| package.root().join(icon));
+        cfg.icon_2x = string("icon-2x").map(|icon| package.root().join(icon));
+    }
+    if cfg.online_redirect_url.is_none() {
+        cfg.online_redirect_url = string("fallback-url");
+    }
+    if cfg.platform_family.is_none() {
+        cfg.platform_family = string("platform-family");
+    }
+    if cfg.keyword.is_none() {
+        cfg.keyword = string("keyword");
+    }
+    if cfg.bundle_identifier.is_none() {
+        cfg.bundle_identifier = string("bundle-identifier");
+    }
+    if cfg.features.is_empty() {
+        cfg.features = string_array(docset.get("features"));
+    }
+}
 
-    Ok(())
+/// Return the names of the rustdoc directories of the crates selected by `spec`.
+fn documented_crate_dirs(workspace: &Workspace, spec: &Packages) -> Result<Vec<String>> {
+    let packages = spec.get_packages(workspace).context(Cargo)?;
+    Ok(packages
+        .iter()
+        .flat_map(|package| package.targets().iter().map(|target| target.crate_name()))
+        .collect())
+}
+
+/// Whether the file at `path` has been modified more recently than the file at `reference`.
+fn is_newer(path: &Path, reference: &Path) -> Result<bool> {
+    let modified = |path: &Path| metadata(path).and_then(|metadata| metadata.modified()).context(IoRead);
+    Ok(modified(path)? > modified(reference)?)
+}
+
+/// Check that the documentation of the packages selected by `spec` has been built previously, and
+/// that it does not look older than the packages' sources.
+fn check_existing_docs(
+    workspace: &Workspace,
+    spec: &Packages,
+    docs_root: &Path
+) -> Result<()> {
+    for package in spec.get_packages(workspace).context(Cargo)? {
+        let mut documented = false;
+        let targets = package.targets().iter().filter(|target| target.documented() && (target.is_lib() || target.is_bin()));
+        for target in targets {
+            let index_page = docs_root.join(target.crate_name()).join("index.html");
+            if !index_page.is_file() {
+                continue;
+            }
+            documented = true;
+            let sources = Some(package.manifest_path()).into_iter().chain(target.src_path().path());
+            for source in sources {
+                if is_newer(source, &index_page)? {
+                    return StaleDocs { crate_name: target.crate_name(), path: source }.fail();
+                }
+            }
+        }
+        if !documented {
+            let crate_name = package.name().as_str().replace('-', "_");
+            return NoDocDir { path: docs_root.join(crate_name) }.fail();
+        }
+    }
+    Ok(())
+}
+
+/// Return the rustdoc directories of the crates selected by `spec`, those of the root package first.
+fn docset_crate_dirs(workspace: &Workspace, package: &Package, spec: &Packages) -> Result<Vec<String>> {
+    let root_dirs = match root_package(workspace, package)? {
+        Some(root_package) => root_package.targets().iter().map(|target| target.crate_name()).collect(),
+        None => vec![]
+    };
+    let mut crate_dirs = documented_crate_dirs(workspace, spec)?;
+    crate_dirs.sort_by_key(|crate_dir| !root_dirs.contains(crate_dir));
+    Ok(crate_dirs)
+}
+
+/// Return the path, relative to the documentation root, of the page the docset opens on: the index
+/// page of the first crate of `crate_dirs` which has one, or else rustdoc's own index page.
+fn index_file_path(docs_root: &Path, crate_dirs: &[String]) -> String {
+    crate_dirs
+        .iter()
+        .find(|crate_dir| docs_root.join(crate_dir).join("index.html").is_file())
+        .map_or_else(|| "index.html".to_owned(), |crate_dir| format!("{}/index.html", crate_dir))
+}
+
+/// Commands which can open a docset in a documentation browser, tried in order.
+#[cfg(target_os = "macos")]
+const OPEN_COMMANDS: &[&[&str]] = &[&["open"]];
+#[cfg(windows)]
+const OPEN_COMMANDS: &[&[&str]] = &[&["cmd", "/C", "start", ""]];
+#[cfg(not(any(target_os = "macos", windows)))]
+const OPEN_COMMANDS: &[&[&str]] = &[&["zeal"], &["xdg-open"]];
+
+/// Open the docset at `docset_root_dir` with the first available documentation browser. Only warn
+/// if there is none, since the docset itself has been generated successfully.
+fn open_docset(cargo_cfg: &CargoConfig, docset_root_dir: &Path) -> Result<()> {
+    for command in OPEN_COMMANDS {
+        let spawned = Command::new(command[0])
+            .args(&command[1..])
+            .arg(docset_root_dir)
+            .spawn();
+        if spawned.is_ok() {
+            cargo_cfg.shell().status("Opening", docset_root_dir.display()).context(Cargo)?;
+            return Ok(());
+        }
+    }
+    cargo_cfg
+        .shell()
+        .warn(format!("cannot open {}: no documentation browser found", docset_root_dir.display()))
+        .context(Cargo)
+}
+
+/// Run the user supplied post-generation hook through the system shell, with the path of the
+/// generated docset in the `CARGO_DOCSET_PATH` environment variable.
+fn run_post_hook(command: &str, docset_root_dir: &Path) -> Result<()> {
+    let mut shell_cmd = if cfg!(windows) {
+        let mut shell_cmd = Command::new("cmd");
+        shell_cmd.arg("/C");
+        shell_cmd
+    } else {
+        let mut shell_cmd = Command::new("sh");
+        shell_cmd.arg("-c");
+        shell_cmd
+    };
+    let status = shell_cmd
+        .arg(command)
+        .env("CARGO_DOCSET_PATH", docset_root_dir)
+        .status()
+        .context(PostHookSpawn)?;
+    if !status.success() {
+        return PostHook { status }.fail();
+    }
+    Ok(())
+}
+
+/// Check that the standard library is installed for `target`, which rustdoc requires.
+fn check_target_installed(cargo_cfg: &CargoConfig, workspace: &Workspace, target: &str) -> Result<()> {
+    let rustc = cargo_cfg.load_global_rustc(Some(workspace)).context(Cargo)?;
+    let output = rustc.process().arg("--print").arg("sysroot").exec_with_output().context(Cargo)?;
+    let sysroot = String::from_utf8_lossy(&output.stdout);
+    let mut target_lib_dir = PathBuf::from(sysroot.trim());
+    target_lib_dir.push("lib");
+    target_lib_dir.push("rustlib");
+    target_lib_dir.push(target);
+    if !target_lib_dir.is_dir() {
+        return TargetNotInstalled { target }.fail();
+    }
+    Ok(())
+}
+
+/// Resolve the packages and features selected by `compile_opts` and report what would be
+/// documented, without building anything.
+fn check_config(
+    cargo_cfg: &CargoConfig,
+    workspace: &Workspace,
+    compile_opts: &CompileOptions,
+    docset_root_dir: &Path
+) -> Result<()> {
+    let specs = compile_opts.spec.to_package_id_specs(workspace).context(Cargo)?;
+    resolve_ws_precisely(
+        workspace,
+        &compile_opts.features,
+        compile_opts.all_features,
+        compile_opts.no_default_features,
+        &specs
+    ).context(Cargo)?;
+    let packages = compile_opts.spec.get_packages(workspace).context(Cargo)?;
+    let rustc = cargo_cfg.load_global_rustc(Some(workspace)).context(Cargo)?;
+
+    let features = if compile_opts.all_features {
+        "all".to_owned()
+    } else {
+        let mut features = if compile_opts.features.is_empty() {
+            "none".to_owned()
+        } else {
+            compile_opts.features.join(" ")
+        };
+        if compile_opts.no_default_features {
+            features.push_str(" (no default features)");
+        }
+        features
+    };
+
+    let mut shell = cargo_cfg.shell();
+    for package in packages {
+        shell.status("Package", format!("{} v{}", package.name(), package.version())).context(Cargo)?;
+    }
+    shell.status("Features", features).context(Cargo)?;
+    let target = compile_opts.build_config.requested_target.clone().unwrap_or(rustc.host);
+    shell.status("Target", target).context(Cargo)?;
+    shell.status("Docset", docset_root_dir.display()).context(Cargo)?;
+    Ok(())
+}
+
+/// Quote and escape a string for inclusion in a JSON document.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c)
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Compiler version and build settings recorded in the docset with `provenance`.
+struct Provenance {
+    rustc_version: String,
+    features: Vec<String>,
+    all_features: bool,
+    no_default_features: bool
+}
+
+/// Record how the docset was generated in a `cargo-docset.json` file at the docset root.
+fn write_provenance<P: AsRef<Path>>(docset_root_dir: P, provenance: &Provenance) -> Result<()> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let features = provenance
+        .features
+        .iter()
+        .map(|f| json_string(f))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut provenance_path = docset_root_dir.as_ref().to_owned();
+    provenance_path.push("cargo-docset.json");
+    let mut provenance_file = File::create(provenance_path).context(IoWrite)?;
+    write!(provenance_file,
+        "\
+        {{
+            \"version\": {},
+            \"timestamp\": {},
+            \"rustc\": {},
+            \"features\": [{}],
+            \"all_features\": {},
+            \"no_default_features\": {}
+        }}
+        ",
+        json_string(env!("CARGO_PKG_VERSION")),
+        timestamp,
+        json_string(&provenance.rustc_version),
+        features,
+        provenance.all_features,
+        provenance.no_default_features).context(IoWrite)?;
+    Ok(())
+}
+
+/// Name of the checksum manifest written at the docset root.
+pub(crate) const MANIFEST_FILE: &str = "manifest.sha256";
+
+/// Return the hexadecimal SHA-256 digest of `data`.
+pub(crate) fn sha256_hex(data: &[u8]) -> String {
+    hex_digest(Algorithm::SHA256, data)
+}
+
+/// Return the hexadecimal SHA-256 digest of the file at `path`.
+pub(crate) fn sha256_file(path: &Path) -> Result<String> {
+    let mut hasher = Hasher::new(Algorithm::SHA256);
+    io::copy(&mut File::open(path).context(IoRead)?, &mut hasher).context(IoRead)?;
+    Ok(hasher.finish().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Add the paths of the files under `dir`, relative to `root_dir`, to `files` in a stable order.
+fn manifest_files(root_dir: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for path in sorted_dir_paths(dir)? {
+        if path.is_dir() {
+            manifest_files(root_dir, &path, files)?;
+        } else if path.is_file() {
+            files.push(path.strip_prefix(root_dir).unwrap().to_owned());
+        }
+    }
+    Ok(())
+}
+
+/// Write the `manifest.sha256` file listing the digest of every file of the docset, in the format
+/// of `sha256sum`, and return the digest of the listing, which identifies the whole docset. The
+/// listing is preceded by a comment line holding that digest.
+fn write_checksum_manifest(docset_root_dir: &Path) -> Result<String> {
+    let manifest_path = docset_root_dir.join(MANIFEST_FILE);
+    if manifest_path.exists() {
+        remove_file(&manifest_path).context(IoWrite)?;
+    }
+    let mut files = vec![];
+    manifest_files(docset_root_dir, docset_root_dir, &mut files)?;
+    // The files are hashed concurrently, the listing keeps their sorted order.
+    let listing = files
+        .par_iter()
+        .map(|file| {
+            let digest = sha256_file(&docset_root_dir.join(file))?;
+            Ok(format!("{}  {}\n", digest, file.to_string_lossy().replace('\\', "/")))
+        })
+        .collect::<Result<Vec<_>>>()?
+        .concat();
+    let digest = sha256_hex(listing.as_bytes());
+    let mut manifest_file = File::create(manifest_path).context(IoWrite)?;
+    write!(manifest_file, "# {}\n{}", digest, listing).context(IoWrite)?;
+    Ok(digest)
+}
+
+/// Split the `features`, each of which may list several features separated with commas or
+/// whitespace, the same way cargo does.
+fn split_features(features: &[String]) -> Vec<String> {
+    features
+        .iter()
+        .flat_map(|features| features.split_whitespace())
+        .flat_map(|features| features.split(','))
+        .filter(|feature| !feature.is_empty())
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
+/// Whether `c` can appear in the keywords Dash scopes the searches with, which are typed before a
+/// colon in the search field.
+fn is_keyword_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_' || c == '.'
+}
+
+/// Return the search keyword derived from `name`: lowercased, with the characters which cannot
+/// appear in a keyword, such as whitespace, replaced with `-`.
+pub(crate) fn search_keyword(name: &str) -> String {
+    name.to_lowercase().chars().map(|c| if is_keyword_char(c) { c } else { '-' }).collect()
+}
+
+/// Whether `name` is a valid search keyword.
+fn is_search_keyword(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(is_keyword_char)
+}
+
+/// Whether `name` is the path `prefix`, or an item located under it.
+fn has_path_prefix(name: &str, prefix: &str, separator: &str) -> bool {
+    name.starts_with(prefix) && (name.len() == prefix.len() || name[prefix.len()..].starts_with(separator))
+}
+
+/// Return the rule selecting the targets named in `targets`, all of them if it is empty, or none if
+/// there is no list.
+fn filter_rule(targets: Option<Vec<String>>) -> FilterRule {
+    match targets {
+        Some(targets) if targets.is_empty() => FilterRule::All,
+        Some(targets) => FilterRule::Just(targets),
+        None => FilterRule::Just(vec![])
+    }
+}
+
+/// Return the specification of the packages selected by `package`, minus those in `exclude`.
+fn package_spec(package: &Package, exclude: &[String]) -> Packages {
+    match package {
+        Package::All if exclude.is_empty() => Packages::All,
+        Package::All => Packages::OptOut(exclude.to_vec()),
+        Package::Current => Packages::Default,
+        Package::Single(name) => Packages::Packages(vec![name.clone()]),
+        Package::List(packages) => Packages::Packages(packages.clone())
+    }
+}
+
+/// Return the spec selecting the packages of `spec` along with the dependencies named `deps`. The
+/// dependencies are resolved by cargo, and may be given as package id specs, such as `rand:0.7.2`.
+fn with_dependencies(workspace: &Workspace, spec: &Packages, deps: &[String]) -> Result<Packages> {
+    let mut packages = spec
+        .get_packages(workspace)
+        .context(Cargo)?
+        .iter()
+        .map(|package| package.name().to_string())
+        .collect::<Vec<_>>();
+    packages.extend(deps.iter().cloned());
+    Ok(Packages::Packages(packages))
+}
+
+/// Check that the settings are valid and compatible with each other.
+fn validate_config(cfg: &GenerateConfig) -> Result<()> {
+    let invalid = |msg| Args { msg }.fail();
+    if cfg.package != Package::All && !cfg.exclude.is_empty() {
+        return invalid("--exclude must be used with --all");
+    }
+    if cfg.jobs == Some(0) {
+        return invalid("--jobs must be a positive integer");
+    }
+    if cfg.entry_limit == Some(0) {
+        return invalid("--entry-limit must be a positive integer");
+    }
+    // Each of the split docsets is named after its package, possibly from its manifest metadata.
+    let is_split = cfg.split && !matches!(cfg.package, Package::Single(_));
+    if is_split && (cfg.docset_name.is_some() || cfg.bundle_identifier.is_some()) {
+        return invalid("--docset-name and --bundle-identifier cannot be used with --split");
+    }
+    if !cfg.only_deps.is_empty() && (cfg.no_dependencies || cfg.split || cfg.linked_deps) {
+        return invalid("--only-deps cannot be used with --no-deps, --split or --linked-deps");
+    }
+    if cfg.temp_dir.is_some() && cfg.incremental {
+        return invalid("--temp-dir cannot be used with --incremental");
+    }
+    if !cfg.extern_urls.is_empty() && cfg.linked_deps {
+        return invalid("--extern-url cannot be used with --linked-deps");
+    }
+    for (crate_name, url) in &cfg.extern_urls {
+        let is_crate_name = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+        if crate_name.is_empty() || !crate_name.chars().all(is_crate_name) {
+            return invalid("--extern-url must be given as CRATE=URL, with a valid crate name");
+        }
+        if url.is_empty() || url.contains(char::is_whitespace) {
+            return invalid("--extern-url must be given as CRATE=URL, with a URL without whitespace");
+        }
+    }
+    if cfg.doc_dir.is_some() {
+        if cfg.docset_name.is_none() {
+            return invalid("--doc-dir requires --docset-name");
+        }
+        let needs_cargo = cfg.split || cfg.linked_deps || !cfg.only_deps.is_empty() || !cfg.extern_urls.is_empty();
+        if needs_cargo || cfg.versioned || cfg.feed.is_some() || cfg.provenance || cfg.check_config {
+            return invalid(
+                "--split, --linked-deps, --only-deps, --extern-url, --versioned, --feed, --provenance and --check-config \
+                 require a cargo build"
+            );
+        }
+    }
+    if let Some(name) = &cfg.docset_name {
+        if name.is_empty() || name == "." || name == ".." || name.contains(is_separator) {
+            return invalid("--docset-name must be a valid file name");
+        }
+    }
+    if let Some(identifier) = &cfg.bundle_identifier {
+        if identifier.is_empty() || identifier.contains(char::is_whitespace) {
+            return invalid("--bundle-identifier must be non-empty and must not contain whitespace");
+        }
+    }
+    if let Some(family) = &cfg.platform_family {
+        if !is_search_keyword(family) {
+            return invalid("--platform-family must be a non-empty keyword of letters, digits, '-', '_' and '.'");
+        }
+    }
+    if let Some(keyword) = &cfg.keyword {
+        if !is_search_keyword(keyword) {
+            return invalid("--keyword must be a non-empty keyword of letters, digits, '-', '_' and '.'");
+        }
+    }
+    // The template is read again when writing the metadata, this only reports its errors early.
+    if let Some(path) = &cfg.plist_template {
+        read_plist_template(path)?;
+    }
+    // rustdoc silently falls back to its default theme when the name is unknown.
+    if let Some(theme) = &cfg.theme {
+        if !RUSTDOC_THEMES.contains(&theme.as_str()) {
+            return invalid("--theme must be one of light, dark or ayu");
+        }
+    }
+    Ok(())
+}
+
+/// Generate a separate docset for each of the selected packages, calling `entry_hook` on the index
+/// entries of each of them, and return the docsets. Each docset only contains the documentation of
+/// its package's crates.
+pub fn generate_split(
+    cargo_cfg: &CargoConfig,
+    workspace: &Workspace,
+    mut cfg: GenerateConfig,
+    entry_hook: Option<EntryHook>
+) -> Result<Vec<GeneratedDocset>> {
+    cfg.split = true;
+    // The settings are validated as given, before the members of a virtual workspace are selected.
+    validate_config(&cfg)?;
+    select_virtual_members(cargo_cfg, workspace, &mut cfg)?;
+    let spec = package_spec(&cfg.package, &cfg.exclude);
+    let packages = spec.get_packages(workspace).context(Cargo)?;
+    let mut docsets = vec![];
+    for (i, package) in packages.iter().enumerate() {
+        let package_cfg = GenerateConfig {
+            package: Package::Single(package.name().as_str().to_owned()),
+            exclude: vec![],
+            // The documentation of the other packages must be kept for the next docsets.
+            clean: cfg.clean && i == 0,
+            ..cfg.clone()
+        };
+        docsets.push(generate_docset(cargo_cfg, workspace, package_cfg, entry_hook)?);
+    }
+    Ok(docsets)
+}
+
+/// Themes built into rustdoc, which can be selected as the default theme of the docset.
+const RUSTDOC_THEMES: &[&str] = &["light", "dark", "ayu"];
+
+/// Hook which can transform, recategorize or drop the index entries before they are written to the
+/// docset index.
+pub type EntryHook<'a> = &'a dyn Fn(&mut Vec<DocsetEntry>);
+
+/// Documentation to package in a docset, and how to name it.
+struct DocsetSource {
+    rustdoc_root_dir: PathBuf,
+    docset_root_dir: PathBuf,
+    docset_name: String,
+    bundle_identifier: String,
+    platform_family: String,
+    keyword: String,
+    /// Crate the online fallback URL points at.
+    fallback_crate: String,
+    /// Rustdoc directories of the documented crates, the one the docset opens on first.
+    crate_dirs: Vec<String>,
+    /// Whether only the documented crates are bundled in the docset.
+    restrict_to_crates: bool,
+    version: Option<String>,
+    icon: Option<PathBuf>,
+    icon_2x: Option<PathBuf>,
+    provenance: Option<Provenance>,
+    /// Duration of the documentation build, if it was built.
+    build_time: Option<Duration>
+}
+
+/// Wall-clock durations of the generation steps, reported with `--timings`.
+struct Timings(Vec<(&'static str, Duration)>);
+
+impl Timings {
+    /// Record the duration of `step`, which started at `start`, and return the start of the next
+    /// step.
+    fn record(&mut self, step: &'static str, start: Instant) -> Instant {
+        let now = Instant::now();
+        self.0.push((step, now - start));
+        now
+    }
+
+    fn report(&self, cargo_cfg: &CargoConfig) -> Result<()> {
+        let total: Duration = self.0.iter().map(|(_, duration)| *duration).sum();
+        // Avoid dividing by zero on coarse clocks.
+        let total_secs = total.as_secs_f64().max(f64::EPSILON);
+        for (step, duration) in &self.0 {
+            let (secs, share) = (duration.as_secs_f64(), duration.as_secs_f64() / total_secs * 100.0);
+            cargo_cfg.shell().status("Timing", format!("{:<10} {:>8.2}s {:>5.1}%", step, secs, share)).context(Cargo)?;
+        }
+        cargo_cfg.shell().status("Timing", format!("{:<10} {:>8.2}s", "total", total.as_secs_f64())).context(Cargo)
+    }
+}
+
+/// Index and copy the documentation of `source` to the docset, and write its metadata.
+fn package_docset(
+    cargo_cfg: &CargoConfig,
+    cfg: &GenerateConfig,
+    source: DocsetSource,
+    entry_hook: Option<EntryHook>
+) -> Result<GeneratedDocset> {
+    let DocsetSource { rustdoc_root_dir, docset_name, .. } = &source;
+    // The docset is built in the temporary directory if there is one, then moved once complete.
+    let staged_dir = cfg.temp_dir.as_ref().map(|temp_dir| temp_dir.join(source.docset_root_dir.file_name().unwrap()));
+    let docset_root_dir = staged_dir.as_ref().unwrap_or(&source.docset_root_dir);
+    let mut timings = Timings(source.build_time.map(|build_time| vec![("build", build_time)]).unwrap_or_default());
+    let mut step_start = Instant::now();
+    let linked_deps = if source.restrict_to_crates {
+        let linked = if cfg.linked_deps {
+            linked_pages(rustdoc_root_dir, &source.crate_dirs)?
+        } else {
+            HashSet::new()
+        };
+        Some((source.crate_dirs.as_slice(), linked))
+    } else {
+        None
+    };
+    let (primary_only, entry_limit, fast_index, incremental) =
+        (cfg.primary_only, cfg.entry_limit, cfg.fast_index, cfg.incremental);
+    let dump_index_path = cfg.dump_index.as_deref();
+    let dry_run = cfg.dry_run;
+    // Inlined re-exports duplicate the page of the item, only the canonical one is indexed.
+    let reexports = if cfg.keep_reexports {
+        HashSet::new()
+    } else {
+        reexport_duplicates(rustdoc_root_dir, &cfg.skip_dirs)?
+    };
+    let (include_paths, exclude_paths, separator) = (&cfg.include_paths, &cfg.exclude_paths, &cfg.name_separator);
+    let (exclude_types, short_aliases) = (&cfg.exclude_types, cfg.short_aliases);
+    let is_indexed = |entry: &DocsetEntry| {
+        if (primary_only && !entry.ty.is_primary()) || exclude_types.contains(&entry.ty) {
+            return false;
+        }
+        if !include_paths.is_empty() && !include_paths.iter().any(|p| has_path_prefix(&entry.name, p, separator)) {
+            return false;
+        }
+        if exclude_paths.iter().any(|p| has_path_prefix(&entry.name, p, separator)) {
+            return false;
+        }
+        if !reexports.is_empty() && reexports.contains(&entry.page_path()) {
+            return false;
+        }
+        match &linked_deps {
+            Some((crate_dirs, linked)) => {
+                let entry_crate = entry.path.components().next().map(|c| c.as_os_str().to_string_lossy());
+                entry_crate.map_or(false, |c| crate_dirs.iter().any(|d| *d == c))
+                    || linked.contains(&entry.page_path())
+            }
+            None => true
+        }
+    };
+
+    // Step 2: iterate over all the html files in the doc directory and parse the filenames
+    // The skipped directories are only left out of the index. All of them are copied, except for the
+    // source pages, which are only bundled with --include-source.
+    cargo_cfg.shell().status("Indexing", rustdoc_root_dir.display()).context(Cargo)?;
+    let walk_progress = progress_bar(cargo_cfg, "Indexing", None);
+    let unrecognized = Mutex::new(BTreeMap::new());
+    let walk_opts = WalkOptions {
+        skip_dirs: &cfg.skip_dirs,
+        separator: &cfg.name_separator,
+        index_members: cfg.index_members && !cfg.primary_only,
+        progress: &walk_progress,
+        unrecognized: &unrecognized
+    };
+    // At this point, we need to start writing into the output docset directory, so create the
+    // hirerarchy, and clean it first if it already exists.
+    let mut docset_hierarchy = docset_root_dir.clone();
+    docset_hierarchy.push("Contents");
+    docset_hierarchy.push("Resources");
+    if !cfg.dry_run {
+        if docset_root_dir.exists() && !cfg.incremental {
+            remove_dir_all(docset_root_dir).context(IoWrite)?;
+        }
+        create_dir_all(&docset_hierarchy).context(IoWrite)?;
+    }
+
+    // Step 3: generate the SQLite database
+    // The entries are written to the index while the documentation is being walked.
+    let (sender, receiver) = sync_channel(ENTRY_CHANNEL_CAPACITY);
+    let mut type_counts = EntryTypeCounts::new();
+    let ignored = thread::scope(|scope| {
+        let (root_dir, walk_opts) = (rustdoc_root_dir, &walk_opts);
+        scope.spawn(move || {
+            if let Err(e) = recursive_walk(root_dir, root_dir, None, walk_opts, &sender) {
+                // The index generation stops on the first error, there is nobody to report the
+                // send failure to.
+                let _ = sender.send(Err(e));
+            }
+        });
+
+        let mut entry_count = 0;
+        let entries = receiver
+            .into_iter()
+            .filter(|entry| entry.as_ref().map_or(true, is_indexed))
+            .map(|entry| {
+                entry_count += 1;
+                match entry_limit {
+                    Some(limit) if entry_count > limit => EntryLimit { limit }.fail(),
+                    _ => entry
+                }
+            });
+        if entry_hook.is_some() || dump_index_path.is_some() || dry_run {
+            // The hook, the dump and the report operate on all the entries at once.
+            let mut entries = entries.collect::<Result<Vec<_>>>()?;
+            // The walk is parallel, restore a stable order.
+            entries.sort_by(|a, b| (&a.name, &a.ty, &a.path).cmp(&(&b.name, &b.ty, &b.path)));
+            if let Some(entry_hook) = entry_hook {
+                entry_hook(&mut entries);
+            }
+            if let Some(dump_index_path) = dump_index_path {
+                dump_index(&entries, dump_index_path)?;
+            }
+            count_entry_types(&entries, &mut type_counts);
+            if dry_run {
+                walk_progress.finish_and_clear();
+                report_dry_run(cargo_cfg, &entries, docset_name, docset_root_dir)?;
+                return Ok(None);
+            }
+            let entries = with_short_aliases(entries.into_iter().map(Ok), short_aliases, separator);
+            generate_sqlite_index(cargo_cfg, docset_root_dir, entries, fast_index, incremental).map(Some)
+        } else {
+            let entries = entries.inspect(|entry| {
+                if let Ok(entry) = entry {
+                    count_entry_types(Some(entry), &mut type_counts);
+                }
+            });
+            let entries = with_short_aliases(entries, short_aliases, separator);
+            generate_sqlite_index(cargo_cfg, docset_root_dir, entries, fast_index, incremental).map(Some)
+        }
+    })?;
+    walk_progress.finish_and_clear();
+    // A new kind of pages is the likely sign of a rustdoc change which the index misses.
+    let unrecognized = unrecognized.into_inner().unwrap();
+    if !unrecognized.is_empty() {
+        let prefixes = unrecognized
+            .iter()
+            .map(|(prefix, count)| format!("{} ({})", prefix, count))
+            .collect::<Vec<_>>()
+            .join(", ");
+        cargo_cfg.shell().warn(format!("pages with unrecognized prefixes were not indexed: {}", prefixes)).context(Cargo)?;
+    }
+    let ignored = match ignored {
+        Some(ignored) => ignored,
+        // Dry run, nothing else to do.
+        None => return Ok(GeneratedDocset { path: source.docset_root_dir.clone(), entry_counts: type_counts })
+    };
+    debug!("{} entries ignored due to conflicts", ignored);
+    if cfg.strict && ignored != 0 {
+        return IndexConflicts { count: ignored }.fail();
+    }
+    // The walk and the index generation run concurrently, they are timed together.
+    step_start = timings.record("index", step_start);
+    if cfg.fts {
+        let fts_progress = progress_bar(cargo_cfg, "Extracting", Some(0));
+        write_fts_index(docset_root_dir, rustdoc_root_dir, &fts_progress)?;
+        fts_progress.finish_and_clear();
+        step_start = timings.record("fts", step_start);
+    }
+
+    // Step 4: Copy the rustdoc to the docset directory
+    docset_hierarchy.push("Documents");
+    cargo_cfg.shell().status("Copying", docset_hierarchy.display()).context(Cargo)?;
+    // The source pages take a lot of space, they can be left out.
+    let skipped_dirs = if cfg.no_source { vec![SOURCE_DIR] } else { vec![] };
+    // With --linked-deps, this overestimates the number of copied files.
+    let copy_progress = progress_bar(cargo_cfg, "Copying", Some(count_files(rustdoc_root_dir, &skipped_dirs)?));
+    let copy_opts = CopyOptions {
+        link: cfg.link,
+        incremental: cfg.incremental,
+        progress: &copy_progress
+    };
+    let linked_deps = linked_deps.as_ref().map(|(crate_dirs, linked)| (*crate_dirs, linked));
+    copy_docs(rustdoc_root_dir, &docset_hierarchy, linked_deps, &skipped_dirs, &copy_opts)?;
+    copy_progress.finish_and_clear();
+    fix_links(&docset_hierarchy)?;
+    if cfg.fix_redirects {
+        fix_redirects(&docset_hierarchy)?;
+    }
+    if cfg.toc {
+        add_toc_anchors(&docset_hierarchy)?;
+    }
+    step_start = timings.record("copy", step_start);
+
+    // Step 5: add the required metadata
+    let plist_template = cfg.plist_template.as_deref().map(read_plist_template).transpose()?;
+    if plist_template.as_ref().map_or(false, |template| !template.contains(OPTIONAL_KEYS_PLACEHOLDER)) {
+        cargo_cfg.shell().warn(format!(
+            "the Info.plist template has no {} placeholder, the keys set by --toc, --enable-js and \
+             --online-redirect-url are left out",
+            OPTIONAL_KEYS_PLACEHOLDER
+        )).context(Cargo)?;
+    }
+    let fallback_url = cfg
+        .online_redirect_url
+        .as_ref()
+        .map(|url| fallback_url(url, &source.fallback_crate, source.version.as_deref().filter(|_| cfg.versioned)));
+    let index_file_path = index_file_path(&docset_hierarchy, &source.crate_dirs);
+    let metadata = DocsetMetadata {
+        docset_name,
+        bundle_identifier: &source.bundle_identifier,
+        index_file_path: &index_file_path,
+        platform_family: &source.platform_family,
+        keyword: &source.keyword,
+        version: if cfg.zeal { source.version.as_deref() } else { None },
+        toc: cfg.toc,
+        fallback_url: fallback_url.as_deref(),
+        zeal: cfg.zeal,
+        javascript: cfg.enable_js,
+        template: plist_template.as_deref()
+    };
+    write_metadata(docset_root_dir, &metadata)?;
+    if let Some(icon) = &source.icon {
+        copy(icon, docset_root_dir.join("icon.png")).context(IoWrite)?;
+    }
+    if let Some(icon_2x) = &source.icon_2x {
+        copy(icon_2x, docset_root_dir.join("icon@2x.png")).context(IoWrite)?;
+    }
+    if let Some(provenance) = &source.provenance {
+        write_provenance(docset_root_dir, provenance)?;
+    }
+    // The manifest covers all the files of the docset, it is written last.
+    if cfg.manifest {
+        let digest = write_checksum_manifest(docset_root_dir)?;
+        cargo_cfg.shell().status("Digest", digest).context(Cargo)?;
+    }
+    if let Some(staged_dir) = &staged_dir {
+        move_docset(staged_dir, &source.docset_root_dir)?;
+    }
+    let docset_root_dir = &source.docset_root_dir;
+
+    // The feeds point at the docset archive.
+    if cfg.compress || cfg.feed.is_some() {
+        write_docset_archive(docset_root_dir, docset_name)?;
+    }
+    if let (Some(base_url), Some(version)) = (&cfg.feed, &source.version) {
+        write_feed(docset_root_dir, docset_name, version, base_url)?;
+    }
+
+    if let Some(command) = &cfg.post_hook {
+        run_post_hook(command, docset_root_dir)?;
+    }
+    timings.record("metadata", step_start);
+
+    cargo_cfg.shell().status("Indexed", entry_summary(&type_counts)).context(Cargo)?;
+    if cfg.timings {
+        timings.report(cargo_cfg)?;
+    }
+    cargo_cfg.shell().status("Finished", docset_root_dir.display()).context(Cargo)?;
+    if cfg.open_after {
+        open_docset(cargo_cfg, docset_root_dir)?;
+    }
+    Ok(GeneratedDocset {
+        path: source.docset_root_dir,
+        entry_counts: type_counts
+    })
+}
+
+/// Generate a docset from the rustdoc output located in `cfg.doc_dir`, such as the output of a
+/// custom rustdoc invocation, without involving cargo. The docset is named after
+/// `cfg.docset_name`, and written next to the documentation directory unless `cfg.output_dir` is
+/// set.
+pub fn generate_from_doc_dir(
+    cargo_cfg: &CargoConfig,
+    cfg: GenerateConfig,
+    entry_hook: Option<EntryHook>
+) -> Result<GeneratedDocset> {
+    validate_config(&cfg)?;
+    let (doc_dir, docset_name) = match (&cfg.doc_dir, &cfg.docset_name) {
+        (Some(doc_dir), Some(docset_name)) => (doc_dir, docset_name.clone()),
+        _ => return Args { msg: "--doc-dir requires --docset-name" }.fail()
+    };
+    if !doc_dir.is_dir() {
+        return NoDocDir { path: doc_dir }.fail();
+    }
+    let rustdoc_root_dir = doc_dir.canonicalize().context(IoRead)?;
+    let mut crate_dirs = vec![];
+    for dir_entry in read_dir(&rustdoc_root_dir).context(IoRead)? {
+        let dir = dir_entry.context(IoRead)?.path();
+        if dir.join("index.html").is_file() {
+            crate_dirs.push(dir.file_name().unwrap().to_string_lossy().into_owned());
+        }
+    }
+    if crate_dirs.is_empty() {
+        return NoDocumentation { path: rustdoc_root_dir }.fail();
+    }
+    // The docset opens on the crate it is named after, if there is one.
+    let main_crate = docset_name.replace('-', "_");
+    crate_dirs.sort_by(|a, b| (*a != main_crate, a).cmp(&(*b != main_crate, b)));
+
+    let mut docset_root_dir = match &cfg.output_dir {
+        Some(output_dir) => output_dir.clone(),
+        None => rustdoc_root_dir.with_file_name("docset")
+    };
+    docset_root_dir.push(format!("{}.docset", docset_name));
+    let (icon, icon_2x) = docset_icons(None, cfg.icon.clone(), cfg.icon_2x.clone())?;
+    let source = DocsetSource {
+        rustdoc_root_dir,
+        docset_root_dir,
+        bundle_identifier: cfg.bundle_identifier.clone().unwrap_or_else(|| docset_name.clone()),
+        platform_family: cfg.platform_family.clone().unwrap_or_else(|| search_keyword(&docset_name)),
+        keyword: cfg.keyword.clone().unwrap_or_else(|| search_keyword(&docset_name)),
+        fallback_crate: main_crate,
+        docset_name,
+        crate_dirs,
+        restrict_to_crates: false,
+        version: None,
+        icon,
+        icon_2x,
+        provenance: None,
+        build_time: None
+    };
+    package_docset(cargo_cfg, &cfg, source, entry_hook)
+}
+
+/// Generate the docset described by `cfg`, and return it. Nothing is written with `check_config`
+/// or `dry_run`, the docset is then the one which would be written. The docsets split with `split`
+/// are generated by [`generate_split`].
+pub fn generate(cargo_cfg: &CargoConfig, workspace: &Workspace, cfg: GenerateConfig) -> Result<GeneratedDocset> {
+    generate_with_hook(cargo_cfg, workspace, cfg, None)
+}
+
+/// Return the name the docset of the `package` selection of `workspace` is named after by default.
+fn root_package_name(workspace: &Workspace, package: &Package) -> Result<String> {
+    Ok(match package {
+        Package::All | Package::List(_) => workspace.root().file_name().unwrap().to_string_lossy().to_string(),
+        Package::Current => workspace.current().context(Cargo)?.name().as_str().to_owned(),
+        Package::Single(name) => name.to_owned()
+    })
+}
+
+/// Return the names of the docsets which can be generated for `workspace` without naming them on
+/// the command line: those named after the workspace or one of its members, or after the name set
+/// in the manifest of a member, with or without the package version.
+pub(crate) fn docset_names(workspace: &Workspace) -> Result<Vec<String>> {
+    let workspace_name = root_package_name(workspace, &Package::All)?;
+    let mut names = vec![];
+    if let Some(current) = workspace.current_opt() {
+        names.push(format!("{} {}", workspace_name, current.version()));
+    }
+    names.push(workspace_name);
+    for package in workspace.members() {
+        let metadata_name = package
+            .manifest()
+            .custom_metadata()
+            .and_then(|metadata| metadata.get("docset"))
+            .and_then(|docset| docset.get("name"))
+            .and_then(TomlValue::as_str);
+        for name in once(package.name().as_str()).chain(metadata_name) {
+            names.push(format!("{} {}", name, package.version()));
+            names.push(name.to_owned());
+        }
+    }
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
+/// Return the directory in which the docsets of `workspace` are written: `output_dir` if it is
+/// set, or the `docset` directory of the target directory.
+pub(crate) fn docset_output_dir(workspace: &Workspace, output_dir: Option<&Path>) -> PathBuf {
+    match output_dir {
+        Some(output_dir) => output_dir.to_owned(),
+        // The target directory honors CARGO_TARGET_DIR and the build.target-dir setting.
+        None => workspace.target_dir().into_path_unlocked().join("docset")
+    }
+}
+
+/// Select all the members of a virtual workspace if no package is selected, as there is no current
+/// package at its root.
+fn select_virtual_members(cargo_cfg: &CargoConfig, workspace: &Workspace, cfg: &mut GenerateConfig) -> Result<()> {
+    if cfg.package == Package::Current && workspace.is_virtual() {
+        cargo_cfg
+            .shell()
+            .status("Documenting", "all the members of the virtual workspace, select some with -p <SPEC>")
+            .context(Cargo)?;
+        cfg.package = Package::All;
+    }
+    Ok(())
+}
+
+/// Generate the docset, calling `entry_hook` on the index entries once the documentation has been
+/// parsed. This is the extension point for custom indexing rules.
+pub fn generate_with_hook(
+    cargo_cfg: &CargoConfig,
+    workspace: &Workspace,
+    mut cfg: GenerateConfig,
+    entry_hook: Option<EntryHook>
+) -> Result<GeneratedDocset> {
+    // Fail before doing any work on invalid settings, as given before the members of a virtual
+    // workspace are selected.
+    validate_config(&cfg)?;
+    select_virtual_members(cargo_cfg, workspace, &mut cfg)?;
+    if cfg.split && !matches!(cfg.package, Package::Single(_)) {
+        return Args { msg: "split docsets must be generated with generate_split" }.fail();
+    }
+    generate_docset(cargo_cfg, workspace, cfg, entry_hook)
+}
+
+/// Generate the docset of the validated `cfg`, which selects a single docset.
+fn generate_docset(
+    cargo_cfg: &CargoConfig,
+    workspace: &Workspace,
+    mut cfg: GenerateConfig,
+    entry_hook: Option<EntryHook>
+) -> Result<GeneratedDocset> {
+    // The settings are taken from the command line first, then from the
+    // [package.metadata.docset] table, then from the [package.metadata.docs.rs] table if requested.
+    let root_package = root_package(workspace, &cfg.package)?;
+    let metadata = root_package.and_then(|package| package.manifest().custom_metadata());
+    if let (Some(package), Some(docset)) = (root_package, metadata.and_then(|metadata| metadata.get("docset"))) {
+        apply_docset_metadata(&mut cfg, package, docset);
+    }
+    if cfg.use_docs_rs_metadata {
+        if let Some(docs_rs) = metadata.and_then(|metadata| metadata.get("docs")).and_then(|docs| docs.get("rs")) {
+            apply_docs_rs_metadata(&mut cfg, docs_rs);
+        }
+    }
+    // The settings read from the manifest must be valid as well.
+    validate_config(&cfg)?;
+
+    // Step 1: generate rustdoc
+    // Figure out for which crate to build the doc and invoke cargo doc.
+    // If no crate is specified, run cargo doc for the current crate/workspace.
+    let compile_mode = CompileMode::Doc {
+        // The dependencies documentation must be built for their linked pages to be bundled, and
+        // is not bundled in split docsets otherwise. The selected dependencies are added to the
+        // documented packages instead, and those with an external URL are linked online.
+        deps: cfg.only_deps.is_empty()
+            && cfg.extern_urls.is_empty()
+            && ((!cfg.no_dependencies && !cfg.split) || cfg.linked_deps)
+    };
+    let mut compile_opts = CompileOptions::new(cargo_cfg, compile_mode).context(CargoDoc)?;
+    if cfg.target.is_some() || cfg.jobs.is_some() {
+        compile_opts.build_config =
+            BuildConfig::new(cargo_cfg, cfg.jobs, &cfg.target, compile_mode).context(CargoDoc)?;
+    }
+    compile_opts.all_features = cfg.all_features;
+    compile_opts.no_default_features = cfg.no_default_features;
+    compile_opts.features = split_features(&cfg.features);
+    if cfg.lib || cfg.bins.is_some() || cfg.examples.is_some() {
+        compile_opts.filter = CompileFilter::Only {
+            all_targets: false,
+            lib: if cfg.lib { LibRule::True } else { LibRule::Default },
+            bins: filter_rule(cfg.bins.take()),
+            examples: filter_rule(cfg.examples.take()),
+            tests: FilterRule::Just(vec![]),
+            benches: FilterRule::Just(vec![]),
+        }
+    }
+    // The RUSTDOCFLAGS environment variable is applied by cargo itself, to all the crates.
+    let mut rustdoc_args = vec![];
+    if cfg.doc_private_items {
+        rustdoc_args.push("--document-private-items".to_owned());
+    }
+    if cfg.docs_rs {
+        // Same as docs.rs, for the crates which gate documentation only attributes behind it.
+        rustdoc_args.push("--cfg".to_owned());
+        rustdoc_args.push("docsrs".to_owned());
+    }
+    if let Some(theme) = &cfg.theme {
+        // Unlike --extern-html-root-url, this option is stable, and must not be passed along with
+        // `-Z unstable-options`, which stable toolchains reject.
+        rustdoc_args.push("--default-theme".to_owned());
+        rustdoc_args.push(theme.clone());
+    }
+    if !cfg.extern_urls.is_empty() {
+        // The option is still unstable, and only accepted by nightly toolchains.
+        rustdoc_args.push("-Z".to_owned());
+        rustdoc_args.push("unstable-options".to_owned());
+        for (crate_name, url) in &cfg.extern_urls {
+            rustdoc_args.push("--extern-html-root-url".to_owned());
+            rustdoc_args.push(format!("{}={}", crate_name.replace('-', "_"), url));
+        }
+    }
+    rustdoc_args.extend(cfg.rustdoc_args.iter().cloned());
+    if !rustdoc_args.is_empty() {
+        compile_opts.local_rustdoc_args = Some(rustdoc_args);
+    }
+    compile_opts.spec = package_spec(&cfg.package, &cfg.exclude);
+    let root_package_name = root_package_name(workspace, &cfg.package)?;
+    // The platform family is the keyword used to restrict a search to the docset in Dash.
+    let platform_family = match &cfg.platform_family {
+        Some(family) => family.clone(),
+        None => search_keyword(&root_package_name)
+    };
+    // The plugin keyword scopes a search to the docset when typed as a `keyword:` prefix.
+    let keyword = cfg.keyword.clone().unwrap_or_else(|| search_keyword(&root_package_name));
+    let base_name = match &cfg.docset_name {
+        Some(name) => name.clone(),
+        None => root_package_name.clone()
+    };
+    // Versioned docsets of the same package can coexist in the same directory.
+    let (docset_name, mut bundle_identifier) = if cfg.versioned {
+        let version = docset_version(workspace, &cfg.package)?;
+        (format!("{} {}", base_name, version), format!("{}-{}", base_name, version))
+    } else {
+        (base_name.clone(), base_name)
+    };
+    // An explicit identifier, such as a reverse DNS name, is used as-is.
+    if let Some(identifier) = &cfg.bundle_identifier {
+        bundle_identifier = identifier.clone();
+    }
+    let mut rustdoc_root_dir = workspace.target_dir().into_path_unlocked();
+    // Cross builds, including those targeting build.target, have their own doc directory.
+    if let Some(target) = &compile_opts.build_config.requested_target {
+        if target.ends_with(".json") {
+            rustdoc_root_dir.push(Path::new(target).file_stem().unwrap());
+        } else {
+            check_target_installed(cargo_cfg, workspace, target)?;
+            rustdoc_root_dir.push(target);
+        }
+    }
+    rustdoc_root_dir.push("doc");
+    let mut docset_root_dir = docset_output_dir(workspace, cfg.output_dir.as_deref());
+    docset_root_dir.push(format!("{}.docset", docset_name));
+
+    let (icon, icon_2x) = docset_icons(Some(workspace.root()), cfg.icon.take(), cfg.icon_2x.take())?;
+
+    if cfg.check_config {
+        check_config(cargo_cfg, workspace, &compile_opts, &docset_root_dir)?;
+        return Ok(GeneratedDocset { path: docset_root_dir, entry_counts: EntryTypeCounts::new() });
+    }
+
+    if cfg.clean && !cfg.no_build {
+        let clean_options = CleanOptions { config: cargo_cfg, spec: vec![], target: None, release: false, doc: true };
+        clean(workspace, &clean_options).context(CargoClean)?;
+    }
+    // Good to go, generate the documentation.
+    let mut doc_cfg = DocOptions {
+        open_result: false,
+        compile_opts
+    };
+    let build_start = Instant::now();
+    let build_time = if cfg.no_build {
+        // Package the documentation previously built by cargo doc.
+        if !rustdoc_root_dir.is_dir() {
+            return NoDocDir { path: rustdoc_root_dir }.fail();
+        }
+        check_existing_docs(workspace, &doc_cfg.compile_opts.spec, &rustdoc_root_dir)?;
+        None
+    } else {
+        // The selected dependencies are only documented, the docset is still that of the packages.
+        let packages_spec = if cfg.only_deps.is_empty() {
+            None
+        } else {
+            let spec = with_dependencies(workspace, &doc_cfg.compile_opts.spec, &cfg.only_deps)?;
+            Some(replace(&mut doc_cfg.compile_opts.spec, spec))
+        };
+        let rustflags = ExtendedRustflags::new(&cfg.rustc_args);
+        doc(workspace, &doc_cfg).context(CargoDoc)?;
+        drop(rustflags);
+        if let Some(spec) = packages_spec {
+            doc_cfg.compile_opts.spec = spec;
+        }
+        Some(build_start.elapsed())
+    };
+    let is_empty = !rustdoc_root_dir.is_dir() || read_dir(&rustdoc_root_dir).context(IoRead)?.next().is_none();
+    if is_empty {
+        return NoDocumentation { path: rustdoc_root_dir }.fail();
+    }
+
+    let crate_dirs = docset_crate_dirs(workspace, &cfg.package, &doc_cfg.compile_opts.spec)?;
+    let version = if cfg.feed.is_some() {
+        Some(docset_version(workspace, &cfg.package)?)
+    } else {
+        docset_version(workspace, &cfg.package).ok()
+    };
+    let provenance = if cfg.provenance {
+        let rustc = cargo_cfg.load_global_rustc(Some(workspace)).context(Cargo)?;
+        Some(Provenance {
+            rustc_version: rustc.verbose_version.lines().next().unwrap_or_default().to_owned(),
+            features: doc_cfg.compile_opts.features.clone(),
+            all_features: doc_cfg.compile_opts.all_features,
+            no_default_features: doc_cfg.compile_opts.no_default_features
+        })
+    } else {
+        None
+    };
+    let source = DocsetSource {
+        rustdoc_root_dir,
+        docset_root_dir,
+        docset_name,
+        bundle_identifier,
+        platform_family,
+        keyword,
+        fallback_crate: root_package_name.replace('-', "_"),
+        // Split docsets are restricted to the documented crates, like with --linked-deps.
+        restrict_to_crates: cfg.linked_deps || cfg.split,
+        crate_dirs,
+        version,
+        icon,
+        icon_2x,
+        provenance,
+        build_time
+    };
+    package_docset(cargo_cfg, &cfg, source, entry_hook)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cargo::core::Shell;
+    use std::process;
+
+    /// Return the entry of the page at `file_path`, relative to the `/doc` rustdoc root, in the
+    /// module `module_path`.
+    fn page_entry(module_path: Option<&str>, file_path: &str, separator: &str) -> Option<DocsetEntry> {
+        parse_docset_entry(&module_path, "/doc", Path::new("/doc").join(file_path), separator)
+    }
+
+    /// Return an entry of type `ty` named `name`, for the page at `path`.
+    fn entry(name: &str, ty: EntryType, path: &str) -> Option<DocsetEntry> {
+        Some(DocsetEntry::new(name.to_owned(), ty, PathBuf::from(path)))
+    }
+
+    /// Create the empty temporary directory of the test `name`.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("cargo-docset-generate-{}-{}", name, process::id()));
+        if dir.exists() {
+            remove_dir_all(&dir).unwrap();
+        }
+        create_dir_all(dir.join("Contents").join("Resources")).unwrap();
+        dir
+    }
+
+    fn cargo_config(dir: &Path) -> CargoConfig {
+        CargoConfig::new(Shell::from_write(Box::new(io::sink())), dir.to_owned(), dir.to_owned())
+    }
+
+    /// Return the files of the index of the docset at `dir`, with its write-ahead log files.
+    fn index_files(dir: &Path) -> Vec<String> {
+        let mut files = read_dir(dir.join("Contents").join("Resources"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        files.sort();
+        files
+    }
+
+    /// Return the rows of the index of the docset at `dir`, in insertion order.
+    fn index_rows(dir: &Path) -> Vec<(String, String, String)> {
+        let conn = Connection::open(dir.join("Contents").join("Resources").join("docSet.dsidx")).unwrap();
+        let mut stmt = conn.prepare("SELECT name, type, path FROM searchIndex ORDER BY id").unwrap();
+        let rows = stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap();
+        rows.collect::<rusqlite::Result<_>>().unwrap()
+    }
+
+    fn index_entry(name: &str, ty: EntryType, path: &str) -> Result<DocsetEntry> {
+        Ok(entry(name, ty, path).unwrap())
+    }
+
+    #[test]
+    fn generate_sqlite_index_removes_the_partial_index_on_error() {
+        let dir = temp_dir("partial-index");
+        let entries = vec![index_entry("geo", EntryType::Package, "geo/index.html"), EntryLimit { limit: 1usize }.fail()];
+        let result = generate_sqlite_index(&cargo_config(&dir), &dir, entries, false, false);
+        assert!(matches!(result, Err(Error::EntryLimit { limit: 1 })));
+        assert!(index_files(&dir).is_empty());
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn generate_sqlite_index_in_memory() {
+        let dir = temp_dir("fast-index");
+        let entries = vec![
+            index_entry("geo::Point", EntryType::Struct, "geo/struct.Point.html"),
+            index_entry("geo", EntryType::Package, "geo/index.html")
+        ];
+        assert_eq!(generate_sqlite_index(&cargo_config(&dir), &dir, entries, true, false).unwrap(), 0);
+        // The in-memory index is backed up with its unique index, in a single file.
+        assert_eq!(index_files(&dir), vec!["docSet.dsidx"]);
+        let conn = Connection::open(dir.join("Contents").join("Resources").join("docSet.dsidx")).unwrap();
+        let anchor: i64 = conn
+            .query_row("SELECT count(*) FROM sqlite_master WHERE type = 'index' AND name = 'anchor'", params![], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(anchor, 1);
+        let count: i64 = conn.query_row("SELECT count(*) FROM searchIndex", params![], |row| row.get(0)).unwrap();
+        assert_eq!(count, 2);
+        drop(conn);
+        let row = |name: &str, ty: &str, path: &str| (name.to_owned(), ty.to_owned(), path.to_owned());
+        assert_eq!(index_rows(&dir), vec![
+            row("geo", "Package", "geo/index.html"),
+            row("geo::Point", "Struct", "geo/struct.Point.html")
+        ]);
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn generate_sqlite_index_inserts_duplicated_entries_once() {
+        let dir = temp_dir("duplicated-entries");
+        let entries = vec![
+            index_entry("geo::Point", EntryType::Struct, "geo/struct.Point.html"),
+            index_entry("geo::Point", EntryType::Struct, "geo/struct.Point.html"),
+            index_entry("geo", EntryType::Package, "geo/index.html")
+        ];
+        assert_eq!(generate_sqlite_index(&cargo_config(&dir), &dir, entries, false, false).unwrap(), 1);
+        assert_eq!(index_rows(&dir).len(), 2);
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn generate_sqlite_index_keeps_the_existing_index_on_error() {
+        let dir = temp_dir("incremental-error");
+        let entries = vec![index_entry("geo", EntryType::Package, "geo/index.html")];
+        generate_sqlite_index(&cargo_config(&dir), &dir, entries, false, false).unwrap();
+        let rows = index_rows(&dir);
+        for &in_memory in &[false, true] {
+            let entries = vec![
+                index_entry("geo::Point", EntryType::Struct, "geo/struct.Point.html"),
+                EntryLimit { limit: 1usize }.fail()
+            ];
+            assert!(generate_sqlite_index(&cargo_config(&dir), &dir, entries, in_memory, true).is_err());
+            assert_eq!(index_files(&dir), vec!["docSet.dsidx"]);
+            assert_eq!(index_rows(&dir), rows);
+        }
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn fallback_url_of_versioned_docsets() {
+        assert_eq!(fallback_url("https://docs.rs/geo/geo/", "geo", None), "https://docs.rs/geo/");
+        assert_eq!(fallback_url("https://example.com/docs", "geo", Some("0.1.0")), "https://example.com/docs/0.1.0/");
+        assert_eq!(fallback_url("https://docs.rs/geo/latest/geo", "geo", Some("0.1.0")), "https://docs.rs/geo/0.1.0/");
+        assert_eq!(fallback_url("https://docs.rs/geo/0.1.0/geo/", "geo", Some("0.1.0")), "https://docs.rs/geo/0.1.0/");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copy_dir_recursive_hard_links_the_files() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = temp_dir("hard-links");
+        let src = dir.join("doc");
+        create_dir_all(src.join("geo")).unwrap();
+        File::create(src.join("geo").join("index.html")).unwrap();
+        let progress = ProgressBar::hidden();
+        let inode = |path: &Path| metadata(path).unwrap().ino();
+        for &link in &[true, false] {
+            let dst = dir.join(format!("docset-{}", link));
+            copy_dir_recursive(&src, &dst, &CopyOptions { link, incremental: false, progress: &progress }).unwrap();
+            let (src_page, dst_page) = (src.join("geo").join("index.html"), dst.join("geo").join("index.html"));
+            assert_eq!(inode(&src_page) == inode(&dst_page), link);
+        }
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn zeal_index_file_paths() {
+        assert_eq!(zeal_index_file_path("geo/index.html"), "geo/index.html");
+        assert_eq!(zeal_index_file_path("./geo/index.html#main"), "geo/index.html");
+        assert_eq!(zeal_index_file_path("/geo\\shapes\\index.html?search=Line"), "geo/shapes/index.html");
+        assert_eq!(zeal_index_file_path("#top"), "index.html");
+    }
+
+    #[test]
+    fn item_page_type_of_prefixes() {
+        assert_eq!(item_page_type("struct"), Some(EntryType::Struct));
+        assert_eq!(item_page_type("fn"), Some(EntryType::Function));
+        assert_eq!(item_page_type("const"), Some(EntryType::Constant));
+        assert_eq!(item_page_type("constant"), Some(EntryType::Constant));
+        assert_eq!(item_page_type("keyword"), None);
+        assert_eq!(item_page_type("foo"), None);
+    }
+
+    #[test]
+    fn unrecognized_page_prefix_of_file_names() {
+        assert_eq!(unrecognized_page_prefix("foo.x.html"), Some("foo"));
+        assert_eq!(unrecognized_page_prefix("traitalias.Shape.html"), Some("traitalias"));
+        assert_eq!(unrecognized_page_prefix("keyword.x.html"), None);
+        assert_eq!(unrecognized_page_prefix("constant.x.html"), None);
+        assert_eq!(unrecognized_page_prefix("struct.Point.html"), None);
+        assert_eq!(unrecognized_page_prefix("index.html"), None);
+        assert_eq!(unrecognized_page_prefix("foo.x.js"), None);
+    }
+
+    #[test]
+    fn parse_docset_entry_of_constant_pages() {
+        assert_eq!(
+            page_entry(Some("geo"), "geo/constant.FOO.html", "::"),
+            entry("geo::FOO", EntryType::Constant, "geo/constant.FOO.html")
+        );
+        assert_eq!(
+            page_entry(Some("geo"), "geo/const.FOO.html", "::"),
+            entry("geo::FOO", EntryType::Constant, "geo/const.FOO.html")
+        );
+    }
+
+    #[test]
+    fn parse_docset_entry_of_pages() {
+        let parse = |module_path, file_path| page_entry(module_path, file_path, "::");
+        assert_eq!(parse(Some("geo"), "geo/index.html"), entry("geo", EntryType::Package, "geo/index.html"));
+        assert_eq!(
+            parse(Some("geo::shapes"), "geo/shapes/index.html"),
+            entry("geo::shapes", EntryType::Module, "geo/shapes/index.html")
+        );
+        assert_eq!(
+            parse(Some("geo::shapes"), "geo/shapes/struct.Line.html"),
+            entry("geo::shapes::Line", EntryType::Struct, "geo/shapes/struct.Line.html")
+        );
+        assert_eq!(parse(None, "index.html"), None);
+        assert_eq!(parse(Some("geo"), "geo/all.html"), None);
+        assert_eq!(parse(Some("geo"), "geo/sidebar-items.js"), None);
+        assert_eq!(parse(Some("geo"), "geo/struct.Point.unknown.html"), None);
+    }
+
+    #[test]
+    fn parse_docset_entry_of_union_pages() {
+        assert_eq!(
+            page_entry(Some("geo"), "geo/union.Bits.html", "::"),
+            entry("geo::Bits", EntryType::Union, "geo/union.Bits.html")
+        );
+    }
+
+    #[test]
+    fn recursive_walk_of_proc_macro_pages() {
+        let dir = temp_dir("walk-proc-macro").join("doc");
+        for page in &["geo_macros/index.html", "geo_macros/attr.route.html", "geo_macros/derive.Shape.html"] {
+            let path = dir.join(page);
+            create_dir_all(path.parent().unwrap()).unwrap();
+            File::create(path).unwrap();
+        }
+        let unrecognized = Mutex::new(BTreeMap::new());
+        let opts = WalkOptions {
+            skip_dirs: &[],
+            separator: "::",
+            index_members: false,
+            progress: &ProgressBar::hidden(),
+            unrecognized: &unrecognized
+        };
+        let (sender, receiver) = sync_channel(ENTRY_CHANNEL_CAPACITY);
+        recursive_walk(&dir, &dir, None, &opts, &sender).unwrap();
+        drop(sender);
+        let mut entries = receiver.into_iter().collect::<Result<Vec<_>>>().unwrap();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(entries, vec![
+            DocsetEntry::new("geo_macros".to_owned(), EntryType::Package, "geo_macros/index.html".into()),
+            DocsetEntry::new("geo_macros::Shape".to_owned(), EntryType::Derive, "geo_macros/derive.Shape.html".into()),
+            DocsetEntry::new("geo_macros::route".to_owned(), EntryType::Attribute, "geo_macros/attr.route.html".into())
+        ]);
+        assert!(unrecognized.into_inner().unwrap().is_empty());
+        remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn parse_docset_entry_with_custom_separator() {
+        assert_eq!(
+            page_entry(Some("geo.shapes"), "geo/shapes/fn.area.html", "."),
+            entry("geo.shapes.area", EntryType::Function, "geo/shapes/fn.area.html")
+        );
+        assert_eq!(
+            page_entry(Some("geo/shapes"), "geo/shapes/struct.Line.html", "/"),
+            entry("geo/shapes/Line", EntryType::Struct, "geo/shapes/struct.Line.html")
+        );
+    }
+
+    #[test]
+    fn with_short_aliases_follows_entries_with_their_alias() {
+        let entry = |name: &str, ty| DocsetEntry::new(name.to_owned(), ty, PathBuf::from("geo/struct.Point.html"));
+        let entries = vec![
+            entry("geo::Point", EntryType::Struct),
+            entry("geo::Point::new", EntryType::Method),
+            entry("geo", EntryType::Package),
+            entry("geo::Point: From<geo::Line>", EntryType::Instance),
+            entry("geo::Point \u{2014} Fields", EntryType::Section)
+        ];
+        let aliased = |enabled| {
+            with_short_aliases(entries.clone().into_iter().map(Ok), enabled, "::")
+                .map(|entry| entry.unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(aliased(false), entries);
+        assert_eq!(aliased(true), vec![
+            entry("geo::Point", EntryType::Struct),
+            entry("Point", EntryType::Struct),
+            entry("geo::Point::new", EntryType::Method),
+            entry("new", EntryType::Method),
+            entry("geo", EntryType::Package),
+            entry("geo::Point: From<geo::Line>", EntryType::Instance),
+            entry("geo::Point \u{2014} Fields", EntryType::Section)
+        ]);
+    }
+
+    #[test]
+    fn with_short_aliases_keeps_errors() {
+        let entries = vec![Err(Error::Args { msg: "invalid" })];
+        let aliased = with_short_aliases(entries, true, "::").collect::<Vec<_>>();
+        assert!(matches!(aliased[..], [Err(Error::Args { msg: "invalid" })]));
+    }
+
+    #[test]
+    fn split_features_on_commas_and_whitespace() {
+        let split = |features: &[&str]| split_features(&features.iter().map(|f| f.to_string()).collect::<Vec<_>>());
+        assert_eq!(split(&["serde"]), vec!["serde"]);
+        assert_eq!(split(&["serde,derive"]), vec!["serde", "derive"]);
+        assert_eq!(split(&["serde derive"]), vec!["serde", "derive"]);
+        assert_eq!(split(&["serde, derive  full", "geo/std"]), vec!["serde", "derive", "full", "geo/std"]);
+        assert_eq!(split(&[",serde,,", " ", ""]), vec!["serde"]);
+        assert!(split(&[]).is_empty());
+    }
+
+    /// Return the message of the argument error of `cfg`, or `None` if it is valid.
+    fn validation_error(cfg: GenerateConfig) -> Option<&'static str> {
+        match validate_config(&cfg) {
+            Ok(()) => None,
+            Err(Error::Args { msg }) => Some(msg),
+            Err(e) => panic!("unexpected error: {}", e)
+        }
+    }
+
+    #[test]
+    fn default_config_enables_the_default_features() {
+        let cfg = GenerateConfig::default();
+        // Like cargo doc, the default features are enabled unless asked otherwise.
+        assert!(!cfg.no_default_features);
+        assert!(!cfg.all_features);
+        assert!(cfg.features.is_empty());
+    }
+
+    #[test]
+    fn validate_config_accepts_defaults() {
+        assert_eq!(validation_error(GenerateConfig::default()), None);
+    }
+
+    #[test]
+    fn validate_config_rejects_invalid_combinations() {
+        let exclude = vec!["geo".to_owned()];
+        assert_eq!(
+            validation_error(GenerateConfig { exclude: exclude.clone(), ..GenerateConfig::default() }),
+            Some("--exclude must be used with --all")
+        );
+        assert_eq!(
+            validation_error(GenerateConfig { package: Package::All, exclude, ..GenerateConfig::default() }),
+            None
+        );
+        assert_eq!(
+            validation_error(GenerateConfig { jobs: Some(0), ..GenerateConfig::default() }),
+            Some("--jobs must be a positive integer")
+        );
+        assert_eq!(
+            validation_error(GenerateConfig { entry_limit: Some(0), ..GenerateConfig::default() }),
+            Some("--entry-limit must be a positive integer")
+        );
+        assert_eq!(
+            validation_error(GenerateConfig {
+                split: true,
+                package: Package::All,
+                docset_name: Some("geo".to_owned()),
+                ..GenerateConfig::default()
+            }),
+            Some("--docset-name and --bundle-identifier cannot be used with --split")
+        );
+        // A split docset of a single package may be renamed.
+        assert_eq!(
+            validation_error(GenerateConfig {
+                split: true,
+                package: Package::Single("geo".to_owned()),
+                docset_name: Some("Geometry".to_owned()),
+                ..GenerateConfig::default()
+            }),
+            None
+        );
+        assert_eq!(
+            validation_error(GenerateConfig { doc_dir: Some("doc".into()), ..GenerateConfig::default() }),
+            Some("--doc-dir requires --docset-name")
+        );
+        assert_eq!(
+            validation_error(GenerateConfig {
+                temp_dir: Some("tmp".into()),
+                incremental: true,
+                ..GenerateConfig::default()
+            }),
+            Some("--temp-dir cannot be used with --incremental")
+        );
+    }
+
+    #[test]
+    fn validate_config_rejects_invalid_values() {
+        let invalid = |keyword: &str| {
+            validation_error(GenerateConfig { keyword: Some(keyword.to_owned()), ..GenerateConfig::default() })
+        };
+        assert_eq!(invalid("geo-rs_1.0"), None);
+        assert!(invalid("geo rs").is_some());
+        assert!(invalid("").is_some());
+        for docset_name in &["", "..", "geo/rs"] {
+            assert_eq!(
+                validation_error(GenerateConfig {
+                    docset_name: Some(docset_name.to_string()),
+                    ..GenerateConfig::default()
+                }),
+                Some("--docset-name must be a valid file name")
+            );
+        }
+        assert_eq!(
+            validation_error(GenerateConfig { theme: Some("solarized".to_owned()), ..GenerateConfig::default() }),
+            Some("--theme must be one of light, dark or ayu")
+        );
+        assert_eq!(
+            validation_error(GenerateConfig {
+                extern_urls: vec![("geo rs".to_owned(), "https://docs.rs/geo".to_owned())],
+                ..GenerateConfig::default()
+            }),
+            Some("--extern-url must be given as CRATE=URL, with a valid crate name")
+        );
+    }
 }
@@ -0,0 +1,34 @@
+//! Implementation of the `list` command.
+
+use crate::{
+    commands::generate::{generate_with_hook, GenerateConfig},
+    common::{DocsetEntry, EntryType},
+    error::*
+};
+
+use cargo::{core::Workspace, Config as CargoConfig};
+
+use std::cell::RefCell;
+
+/// Return the entries which would be indexed in the docset of `cfg`, of the given `types` only if
+/// it is not empty, without writing anything. The existing documentation is used as-is, it is not
+/// built again.
+pub fn list_entries(
+    cargo_cfg: &CargoConfig,
+    workspace: &Workspace,
+    cfg: GenerateConfig,
+    types: &[EntryType]
+) -> Result<Vec<DocsetEntry>> {
+    let cfg = GenerateConfig {
+        no_build: true,
+        dry_run: true,
+        ..cfg
+    };
+    let listed = RefCell::new(vec![]);
+    let hook = |entries: &mut Vec<DocsetEntry>| {
+        entries.retain(|entry| types.is_empty() || types.contains(&entry.ty));
+        listed.borrow_mut().extend(entries.iter().cloned());
+    };
+    generate_with_hook(cargo_cfg, workspace, cfg, Some(&hook))?;
+    Ok(listed.into_inner())
+}
@@ -0,0 +1,136 @@
+//! Implementation of the `merge` command.
+
+use crate::{
+    commands::{
+        generate::{
+            copy_dir_recursive, generate_sqlite_index, progress_bar, search_keyword, write_metadata, CopyOptions,
+            DocsetMetadata
+        },
+        verify::plist_string
+    },
+    common::{DocsetEntry, EntryType},
+    error::*
+};
+
+use cargo::Config as CargoConfig;
+use rusqlite::{params, Connection, OpenFlags};
+use snafu::ResultExt;
+
+use std::{
+    collections::HashSet,
+    fs::{create_dir_all, read_to_string, remove_dir_all},
+    path::{Path, PathBuf}
+};
+
+/// Return the index entries of the docset at `docset_dir`, with their paths prefixed with
+/// `subdir`. Return the number of entries of unknown types, which are not returned, as well.
+fn prefixed_entries(docset_dir: &Path, subdir: &str) -> Result<(Vec<DocsetEntry>, usize)> {
+    let conn_path = docset_dir.join("Contents").join("Resources").join("docSet.dsidx");
+    if !conn_path.is_file() {
+        return InvalidDocset { path: conn_path, msg: "missing index" }.fail();
+    }
+    let conn = Connection::open_with_flags(&conn_path, OpenFlags::SQLITE_OPEN_READ_ONLY).context(Sqlite)?;
+    let mut stmt = conn.prepare("SELECT name, type, path FROM searchIndex").context(Sqlite)?;
+    let rows = stmt
+        .query_map(params![], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))
+        .context(Sqlite)?;
+    let (mut entries, mut unknown) = (vec![], 0);
+    for row in rows {
+        let (name, ty, path) = row.context(Sqlite)?;
+        match EntryType::from_dash_name(&ty) {
+            Some(ty) => entries.push(DocsetEntry::new(name, ty, PathBuf::from(format!("{}/{}", subdir, path)))),
+            None => unknown += 1
+        }
+    }
+    Ok((entries, unknown))
+}
+
+/// Merge the `docsets` into a single docset written at `output`, which is named after its file
+/// stem. The documentation of each docset is copied to its own subdirectory, named after the
+/// docset and suffixed in case of collisions, and the paths of its index entries are prefixed
+/// accordingly. The merged docset opens on the index page of the first docset.
+pub fn merge_docsets(cargo_cfg: &CargoConfig, output: &Path, docsets: &[PathBuf]) -> Result<PathBuf> {
+    let output = if output.extension().map_or(false, |extension| extension == "docset") {
+        output.to_owned()
+    } else {
+        let mut output = output.as_os_str().to_owned();
+        output.push(".docset");
+        PathBuf::from(output)
+    };
+    let docset_name = output.file_stem().unwrap().to_string_lossy().into_owned();
+    if docsets.iter().any(|docset| docset == &output) {
+        return Args { msg: "the merged docset cannot be one of the merged docsets" }.fail();
+    }
+
+    // Read all the sources first, so that nothing is written if one of them is invalid.
+    let mut subdirs = HashSet::new();
+    let mut sources = vec![];
+    for docset in docsets {
+        let stem = docset.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let mut subdir = stem.clone();
+        let mut suffix = 1;
+        while !subdirs.insert(subdir.clone()) {
+            suffix += 1;
+            subdir = format!("{}-{}", stem, suffix);
+        }
+        let info_plist_path = docset.join("Contents").join("Info.plist");
+        let info_plist = read_to_string(&info_plist_path).context(IoRead)?;
+        let index_file_path = match plist_string(&info_plist, "dashIndexFilePath") {
+            Some(index_file_path) => index_file_path,
+            None => return InvalidDocset { path: info_plist_path, msg: "missing the dashIndexFilePath key" }.fail()
+        };
+        let (entries, unknown) = prefixed_entries(docset, &subdir)?;
+        if unknown != 0 {
+            cargo_cfg.shell().warn(format!(
+                "{} entries of unknown types in {} were not merged",
+                unknown,
+                docset.display()
+            )).context(Cargo)?;
+        }
+        sources.push((docset, subdir, index_file_path, entries));
+    }
+
+    if output.exists() {
+        remove_dir_all(&output).context(IoWrite)?;
+    }
+    let documents_dir = output.join("Contents").join("Resources").join("Documents");
+    create_dir_all(&documents_dir).context(IoWrite)?;
+    let copy_progress = progress_bar(cargo_cfg, "Copying", None);
+    let copy_opts = CopyOptions {
+        link: false,
+        incremental: false,
+        progress: &copy_progress
+    };
+    for (docset, subdir, _, _) in &sources {
+        cargo_cfg.shell().status("Merging", docset.display()).context(Cargo)?;
+        let src = docset.join("Contents").join("Resources").join("Documents");
+        copy_dir_recursive(src, documents_dir.join(subdir), &copy_opts)?;
+    }
+    copy_progress.finish_and_clear();
+
+    let index_file_path = sources
+        .first()
+        .map_or_else(|| "index.html".to_owned(), |(_, subdir, path, _)| format!("{}/{}", subdir, path));
+    let entries = sources.into_iter().flat_map(|(_, _, _, entries)| entries).map(Ok);
+    let ignored = generate_sqlite_index(cargo_cfg, &output, entries, false, false)?;
+    if ignored != 0 {
+        cargo_cfg.shell().warn(format!("{} duplicated entries were dropped", ignored)).context(Cargo)?;
+    }
+    let keyword = search_keyword(&docset_name);
+    let metadata = DocsetMetadata {
+        docset_name: &docset_name,
+        bundle_identifier: &keyword,
+        index_file_path: &index_file_path,
+        platform_family: &keyword,
+        keyword: &keyword,
+        version: None,
+        toc: false,
+        fallback_url: None,
+        zeal: false,
+        javascript: false,
+        template: None
+    };
+    write_metadata(&output, &metadata)?;
+    cargo_cfg.shell().status("Finished", output.display()).context(Cargo)?;
+    Ok(output)
+}
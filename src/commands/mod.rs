@@ -1 +1,6 @@
+pub mod clean;
 pub mod generate;
+pub mod list;
+pub mod merge;
+pub mod verify;
+pub mod watch;
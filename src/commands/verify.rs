@@ -0,0 +1,130 @@
+//! Implementation of the `verify` command.
+
+use crate::{
+    commands::generate::{sha256_file, sha256_hex, MANIFEST_FILE},
+    error::*
+};
+
+use cargo::Config as CargoConfig;
+use rusqlite::{params, Connection, OpenFlags};
+use snafu::ResultExt;
+
+use std::{fs::read_to_string, path::Path};
+
+/// Return the string value of `key` in the Info.plist `contents`, or `None` if the key is missing.
+pub(crate) fn plist_string(contents: &str, key: &str) -> Option<String> {
+    let key_end = contents.find(&format!("<key>{}</key>", key))? + key.len() + "<key></key>".len();
+    let value = contents[key_end..].trim_start().strip_prefix("<string>")?;
+    let value = &value[..value.find("</string>")?];
+    Some(value.replace("&lt;", "<").replace("&gt;", ">").replace("&amp;", "&"))
+}
+
+/// Check the Info.plist of the docset, and return the path of its index page, relative to the
+/// documentation directory.
+fn verify_info_plist(docset_dir: &Path) -> Result<String> {
+    let path = docset_dir.join("Contents").join("Info.plist");
+    let invalid = |msg| InvalidDocset { path: &path, msg }.fail();
+    let contents = match read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(_) => return invalid("cannot read the file")
+    };
+    if !contents.contains("<plist") || !contents.contains("</plist>") {
+        return invalid("not a property list");
+    }
+    for key in &["CFBundleIdentifier", "CFBundleName", "DocSetPlatformFamily"] {
+        if plist_string(&contents, key).is_none() {
+            return invalid("missing the CFBundleIdentifier, CFBundleName or DocSetPlatformFamily key");
+        }
+    }
+    match plist_string(&contents, "dashIndexFilePath") {
+        Some(index_file_path) => Ok(index_file_path),
+        None => invalid("missing the dashIndexFilePath key")
+    }
+}
+
+/// Check the files of the docset against its checksum manifest, and return the number of those
+/// which are missing or were modified.
+fn verify_manifest(cargo_cfg: &CargoConfig, docset_dir: &Path, manifest: &str) -> Result<usize> {
+    let manifest_path = docset_dir.join(MANIFEST_FILE);
+    let (digest, listing) = match manifest.strip_prefix("# ").and_then(|manifest| manifest.split_once('\n')) {
+        Some(parts) => parts,
+        None => return InvalidDocset { path: manifest_path, msg: "missing the digest of the manifest" }.fail()
+    };
+    if sha256_hex(listing.as_bytes()) != digest {
+        return InvalidDocset { path: manifest_path, msg: "the manifest does not match its digest" }.fail();
+    }
+    let mut broken = 0;
+    for line in listing.lines() {
+        let (expected, file) = match line.split_once("  ") {
+            Some(parts) => parts,
+            None => return InvalidDocset { path: manifest_path, msg: "malformed manifest line" }.fail()
+        };
+        let path = docset_dir.join(file);
+        if !path.is_file() {
+            cargo_cfg.shell().error(format!("file {} listed in the manifest does not exist", file)).context(Cargo)?;
+            broken += 1;
+        } else if sha256_file(&path)? != expected {
+            cargo_cfg.shell().error(format!("file {} does not match the manifest", file)).context(Cargo)?;
+            broken += 1;
+        }
+    }
+    Ok(broken)
+}
+
+/// Check that the docset at `docset_dir` is usable: its Info.plist must be complete, and its index
+/// must have the expected schema. Each index entry, and the index page, must point at a bundled
+/// page. The files must match the checksum manifest, if there is one. The broken entries and files are
+/// reported, and make the verification fail.
+pub fn verify_docset(cargo_cfg: &CargoConfig, docset_dir: &Path) -> Result<()> {
+    let documents_dir = docset_dir.join("Contents").join("Resources").join("Documents");
+    let mut broken: usize = 0;
+
+    let index_file_path = verify_info_plist(docset_dir)?;
+    if !documents_dir.join(&index_file_path).is_file() {
+        cargo_cfg.shell().error(format!("index page {} does not exist", index_file_path)).context(Cargo)?;
+        broken += 1;
+    }
+
+    let conn_path = docset_dir.join("Contents").join("Resources").join("docSet.dsidx");
+    if !conn_path.is_file() {
+        return InvalidDocset { path: conn_path, msg: "missing index" }.fail();
+    }
+    let conn = Connection::open_with_flags(&conn_path, OpenFlags::SQLITE_OPEN_READ_ONLY).context(Sqlite)?;
+    let schema_count = |kind: &str, name: &str| -> Result<i64> {
+        conn.query_row(
+            "SELECT count(*) FROM sqlite_master WHERE type = ?1 AND name = ?2",
+            params![kind, name],
+            |row| row.get(0)
+        )
+        .context(Sqlite)
+    };
+    if schema_count("table", "searchIndex")? == 0 {
+        return InvalidDocset { path: conn_path, msg: "missing the searchIndex table" }.fail();
+    }
+    if schema_count("index", "anchor")? == 0 {
+        return InvalidDocset { path: conn_path, msg: "missing the unique index of the searchIndex table" }.fail();
+    }
+
+    let mut stmt = conn.prepare("SELECT name, type, path FROM searchIndex ORDER BY name, type, path").context(Sqlite)?;
+    let rows = stmt
+        .query_map(params![], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))
+        .context(Sqlite)?;
+    let mut entries = 0;
+    for row in rows {
+        let (name, ty, path) = row.context(Sqlite)?;
+        entries += 1;
+        let page = path.split('#').next().unwrap();
+        if !documents_dir.join(page).is_file() {
+            cargo_cfg.shell().error(format!("{} entry {} points at missing page {}", ty, name, page)).context(Cargo)?;
+            broken += 1;
+        }
+    }
+    if let Ok(manifest) = read_to_string(docset_dir.join(MANIFEST_FILE)) {
+        broken += verify_manifest(cargo_cfg, docset_dir, &manifest)?;
+    }
+    if broken != 0 {
+        return BrokenDocset { count: broken }.fail();
+    }
+    cargo_cfg.shell().status("Verified", format!("{} entries in {}", entries, docset_dir.display())).context(Cargo)?;
+    Ok(())
+}
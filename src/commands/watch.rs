@@ -0,0 +1,103 @@
+//! Implementation of the `--watch` mode.
+
+use crate::{
+    commands::generate::{generate, generate_split, GenerateConfig},
+    error::*
+};
+
+use cargo::{core::Workspace, Config as CargoConfig};
+use snafu::ResultExt;
+
+use std::{
+    fs::{metadata, read_dir},
+    path::{Path, PathBuf},
+    thread::sleep,
+    time::{Duration, SystemTime}
+};
+
+/// Interval between two checks of the watched files.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Modification times of the watched files, used to detect their changes.
+type Snapshot = Vec<(PathBuf, SystemTime)>;
+
+/// Add the modification time of `path` to `snapshot`, or of all the files under `path` if it is a
+/// directory. Missing paths are ignored, they may be created later on.
+fn snapshot_path(path: &Path, snapshot: &mut Snapshot) -> Result<()> {
+    if path.is_dir() {
+        for dir_entry in read_dir(path).context(IoRead)? {
+            snapshot_path(&dir_entry.context(IoRead)?.path(), snapshot)?;
+        }
+    } else if path.is_file() {
+        let modified = metadata(path).and_then(|metadata| metadata.modified()).context(IoRead)?;
+        snapshot.push((path.to_owned(), modified));
+    }
+    Ok(())
+}
+
+/// Take a snapshot of the manifests and sources of the members of the workspace of `root_manifest`.
+fn snapshot(cargo_cfg: &CargoConfig, root_manifest: &Path) -> Result<Snapshot> {
+    let mut snapshot = vec![];
+    snapshot_path(root_manifest, &mut snapshot)?;
+    // A broken manifest is reported by the generation, which is needed to fix it.
+    if let Ok(workspace) = Workspace::new(root_manifest, cargo_cfg) {
+        for member in workspace.members() {
+            snapshot_path(member.manifest_path(), &mut snapshot)?;
+            snapshot_path(&member.root().join("src"), &mut snapshot)?;
+        }
+    }
+    snapshot.sort();
+    snapshot.dedup();
+    Ok(snapshot)
+}
+
+/// Generate the docset of the workspace of `root_manifest`, then generate it again each time the
+/// manifests or the sources of the workspace members change. The regenerations are incremental,
+/// and their failures are reported without ending the watch. This never returns on success.
+pub fn watch(cargo_cfg: &CargoConfig, root_manifest: &Path, mut cfg: GenerateConfig) -> Result<()> {
+    if cfg.dry_run || cfg.check_config || cfg.doc_dir.is_some() {
+        return Args { msg: "--watch cannot be used with --dry-run, --check-config or --doc-dir" }.fail();
+    }
+    let mut snapshot = snapshot(cargo_cfg, root_manifest)?;
+    loop {
+        // The workspace is reloaded, in case the manifests changed.
+        let generated = Workspace::new(root_manifest, cargo_cfg)
+            .context(CargoConfig)
+            .and_then(|workspace| {
+                if cfg.split {
+                    generate_split(cargo_cfg, &workspace, cfg.clone(), None).map(|_| ())
+                } else {
+                    generate(cargo_cfg, &workspace, cfg.clone()).map(|_| ())
+                }
+            });
+        if let Err(e) = generated {
+            cargo_cfg.shell().error(e).context(Cargo)?;
+        }
+        // The docset is only opened once, and updated in place afterwards.
+        cfg.open_after = false;
+        cfg.clean = false;
+        // A docset built in a temporary directory is always built from scratch.
+        cfg.incremental = cfg.temp_dir.is_none();
+        cargo_cfg.shell().status("Watching", format!("{} files for changes", snapshot.len())).context(Cargo)?;
+
+        // Wait for the files to change, then for them to settle, so that a burst of saves triggers
+        // a single generation.
+        loop {
+            sleep(POLL_INTERVAL);
+            let current = self::snapshot(cargo_cfg, root_manifest)?;
+            if current != snapshot {
+                snapshot = current;
+                break;
+            }
+        }
+        loop {
+            sleep(POLL_INTERVAL);
+            let current = self::snapshot(cargo_cfg, root_manifest)?;
+            if current == snapshot {
+                break;
+            }
+            snapshot = current;
+        }
+        cargo_cfg.shell().status("Changed", "regenerating the docset").context(Cargo)?;
+    }
+}
@@ -1,4 +1,5 @@
 use derive_more::Constructor;
+use serde::Serialize;
 
 use std::{fmt::Display, path::PathBuf};
 
@@ -10,38 +11,243 @@ pub enum Package {
     List(Vec<String>)
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
 pub enum EntryType {
+    AssociatedConstant,
+    AssociatedType,
+    Attribute,
     Constant,
+    Derive,
     Enum,
     Function,
+    Field,
+    Instance, // i.e. trait implementation
     Macro,
+    Method,
     Module,
     Package, // i.e. crate
+    Primitive, // i.e. builtin type
+    Section, // i.e. heading of a page
+    Static,
     Struct,
     Trait,
-    Type //Union // Is this even implemented in Rust ?
+    Type,
+    Union,
+    Variant
+}
+
+impl EntryType {
+    /// Whether entries of this type are derived from a rustdoc page file name, as opposed to an
+    /// anchor inside a page.
+    pub fn is_primary(&self) -> bool {
+        match self {
+            EntryType::Attribute
+            | EntryType::Constant
+            | EntryType::Derive
+            | EntryType::Enum
+            | EntryType::Function
+            | EntryType::Macro
+            | EntryType::Module
+            | EntryType::Package
+            | EntryType::Primitive
+            | EntryType::Static
+            | EntryType::Struct
+            | EntryType::Trait
+            | EntryType::Type
+            | EntryType::Union => true,
+            EntryType::AssociatedConstant
+            | EntryType::AssociatedType
+            | EntryType::Field
+            | EntryType::Instance
+            | EntryType::Method
+            | EntryType::Section
+            | EntryType::Variant => false
+        }
+    }
+
+    /// Whether the pages of entries of this type document members (methods, fields, variants) and
+    /// sections which can be indexed.
+    pub fn has_members(&self) -> bool {
+        matches!(self, EntryType::Enum | EntryType::Primitive | EntryType::Struct | EntryType::Trait | EntryType::Union)
+    }
+
+    /// Human readable name of the type, used in the reports.
+    pub fn description(&self) -> &'static str {
+        match self {
+            EntryType::AssociatedConstant => "associated constant",
+            EntryType::AssociatedType => "associated type",
+            EntryType::Attribute => "attribute macro",
+            EntryType::Constant => "constant",
+            EntryType::Derive => "derive macro",
+            EntryType::Enum => "enum",
+            EntryType::Function => "function",
+            EntryType::Field => "field",
+            EntryType::Instance => "trait implementation",
+            EntryType::Macro => "macro",
+            EntryType::Method => "method",
+            EntryType::Module => "module",
+            EntryType::Package => "package",
+            EntryType::Primitive => "primitive type",
+            EntryType::Section => "section",
+            EntryType::Static => "static",
+            EntryType::Struct => "struct",
+            EntryType::Trait => "trait",
+            EntryType::Type => "type alias",
+            EntryType::Union => "union",
+            EntryType::Variant => "variant"
+        }
+    }
+
+    /// Parse the kind of entries named `kind` on the command line, such as `method` or
+    /// `associated-type`.
+    pub fn from_kind(kind: &str) -> Option<EntryType> {
+        Some(match kind {
+            "associated-constant" => EntryType::AssociatedConstant,
+            "associated-type" => EntryType::AssociatedType,
+            "attribute" => EntryType::Attribute,
+            "constant" => EntryType::Constant,
+            "derive" => EntryType::Derive,
+            "enum" => EntryType::Enum,
+            "function" => EntryType::Function,
+            "field" => EntryType::Field,
+            "impl" => EntryType::Instance,
+            "macro" => EntryType::Macro,
+            "method" => EntryType::Method,
+            "module" => EntryType::Module,
+            "package" => EntryType::Package,
+            "primitive" => EntryType::Primitive,
+            "section" => EntryType::Section,
+            "static" => EntryType::Static,
+            "struct" => EntryType::Struct,
+            "trait" => EntryType::Trait,
+            "type" => EntryType::Type,
+            "union" => EntryType::Union,
+            "variant" => EntryType::Variant,
+            _ => return None
+        })
+    }
+
+    /// Parse the Dash type name of an index entry, as written by `Display`. The types which share
+    /// a Dash name, such as the constants and the associated constants, are parsed as the item
+    /// type.
+    pub fn from_dash_name(name: &str) -> Option<EntryType> {
+        Some(match name {
+            "Attribute" => EntryType::Attribute,
+            "Constant" => EntryType::Constant,
+            "Procedure" => EntryType::Derive,
+            "Enum" => EntryType::Enum,
+            "Function" => EntryType::Function,
+            "Field" => EntryType::Field,
+            "Instance" => EntryType::Instance,
+            "Macro" => EntryType::Macro,
+            "Method" => EntryType::Method,
+            "Module" => EntryType::Module,
+            "Package" => EntryType::Package,
+            "Builtin" => EntryType::Primitive,
+            "Section" => EntryType::Section,
+            "Global" => EntryType::Static,
+            "Struct" => EntryType::Struct,
+            "Trait" => EntryType::Trait,
+            "Type" => EntryType::Type,
+            "Union" => EntryType::Union,
+            "Variant" => EntryType::Variant,
+            _ => return None
+        })
+    }
+
+    /// Whether this is the type of items defined in impl blocks or traits.
+    pub fn is_associated_item(&self) -> bool {
+        matches!(self, EntryType::AssociatedConstant | EntryType::AssociatedType | EntryType::Method)
+    }
 }
 
 impl Display for EntryType {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
         match self {
+            EntryType::AssociatedConstant => write!(f, "Constant"),
+            EntryType::AssociatedType => write!(f, "Type"),
+            EntryType::Attribute => write!(f, "Attribute"),
             EntryType::Constant => write!(f, "Constant"),
+            EntryType::Derive => write!(f, "Procedure"),
             EntryType::Enum => write!(f, "Enum"),
             EntryType::Function => write!(f, "Function"),
+            EntryType::Field => write!(f, "Field"),
+            EntryType::Instance => write!(f, "Instance"),
             EntryType::Macro => write!(f, "Macro"),
+            EntryType::Method => write!(f, "Method"),
             EntryType::Module => write!(f, "Module"),
             EntryType::Package => write!(f, "Package"),
+            EntryType::Primitive => write!(f, "Builtin"),
+            EntryType::Section => write!(f, "Section"),
+            EntryType::Static => write!(f, "Global"),
             EntryType::Struct => write!(f, "Struct"),
             EntryType::Trait => write!(f, "Trait"),
-            EntryType::Type => write!(f, "Type")
+            EntryType::Type => write!(f, "Type"),
+            EntryType::Union => write!(f, "Union"),
+            EntryType::Variant => write!(f, "Variant")
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Constructor)]
+#[derive(Debug, Clone, PartialEq, Eq, Constructor, Serialize)]
 pub struct DocsetEntry {
     pub name: String,
     pub ty: EntryType,
     pub path: PathBuf
 }
+
+impl DocsetEntry {
+    /// Path of the page of this entry, without the anchor of member entries.
+    pub fn page_path(&self) -> PathBuf {
+        let path = self.path.to_string_lossy();
+        PathBuf::from(path.split('#').next().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENTRY_TYPES: &[EntryType] = &[
+        EntryType::AssociatedConstant,
+        EntryType::AssociatedType,
+        EntryType::Attribute,
+        EntryType::Constant,
+        EntryType::Derive,
+        EntryType::Enum,
+        EntryType::Function,
+        EntryType::Field,
+        EntryType::Instance,
+        EntryType::Macro,
+        EntryType::Method,
+        EntryType::Module,
+        EntryType::Package,
+        EntryType::Primitive,
+        EntryType::Section,
+        EntryType::Static,
+        EntryType::Struct,
+        EntryType::Trait,
+        EntryType::Type,
+        EntryType::Union,
+        EntryType::Variant
+    ];
+
+    #[test]
+    fn from_dash_name_parses_display() {
+        for ty in ENTRY_TYPES {
+            let expected = match ty {
+                EntryType::AssociatedConstant => EntryType::Constant,
+                EntryType::AssociatedType => EntryType::Type,
+                ty => ty.clone()
+            };
+            assert_eq!(EntryType::from_dash_name(&ty.to_string()), Some(expected));
+        }
+    }
+
+    #[test]
+    fn from_dash_name_rejects_unknown_names() {
+        assert_eq!(EntryType::from_dash_name("Class"), None);
+        assert_eq!(EntryType::from_dash_name("struct"), None);
+        assert_eq!(EntryType::from_dash_name(""), None);
+    }
+}
@@ -69,6 +69,73 @@ pub enum Error {
     #[snafu(display("Invalid arguments: {}", msg))]
     Args {
         msg: &'static str
+    },
+    #[snafu(display("{} index entries conflicted with an existing entry and were dropped", count))]
+    IndexConflicts {
+        count: usize
+    },
+    #[snafu(display("The index exceeds the limit of {} entries", limit))]
+    EntryLimit {
+        limit: usize
+    },
+    #[snafu(display("Cannot run the post-generation hook: {}", source))]
+    PostHookSpawn {
+        source: std::io::Error
+    },
+    #[snafu(display("Post-generation hook failed: {}", status))]
+    PostHook {
+        status: std::process::ExitStatus
+    },
+    #[snafu(display("Invalid icon {}: {}", path.display(), msg))]
+    Icon {
+        path: std::path::PathBuf,
+        msg: &'static str
+    },
+    #[snafu(display("Cannot dump the index: {}", source))]
+    DumpIndex {
+        source: serde_json::Error
+    },
+    #[snafu(display("The documentation directory {} does not exist, build the documentation first", path.display()))]
+    NoDocDir {
+        path: std::path::PathBuf
+    },
+    #[snafu(display(
+        "The documentation of {} is older than {}, rebuild it by running without --no-build",
+        crate_name,
+        path.display()
+    ))]
+    StaleDocs {
+        crate_name: String,
+        path: std::path::PathBuf
+    },
+    #[snafu(display(
+        "No documentation was generated in {}, did the build fail or produce no public items?",
+        path.display()
+    ))]
+    NoDocumentation {
+        path: std::path::PathBuf
+    },
+    #[snafu(display("Path {} is not valid UTF-8", path.display()))]
+    NonUtf8Path {
+        path: std::path::PathBuf
+    },
+    #[snafu(display("The Info.plist template {} lacks the {} placeholder", path.display(), placeholder))]
+    PlistTemplate {
+        path: std::path::PathBuf,
+        placeholder: &'static str
+    },
+    #[snafu(display("Invalid docset, {}: {}", path.display(), msg))]
+    InvalidDocset {
+        path: std::path::PathBuf,
+        msg: &'static str
+    },
+    #[snafu(display("{} broken entries or files were found in the docset", count))]
+    BrokenDocset {
+        count: usize
+    },
+    #[snafu(display("Target {} is not installed, try `rustup target add {}`", target, target))]
+    TargetNotInstalled {
+        target: String
     }
 }
 
@@ -0,0 +1,735 @@
+//! Parsing and post-processing of the rustdoc HTML pages.
+
+use crate::{
+    common::{DocsetEntry, EntryType},
+    error::*
+};
+
+use scraper::{Html, Selector};
+use snafu::ResultExt;
+
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    fs::{read_dir, read_to_string, remove_file, write},
+    path::{Component, Path, PathBuf}
+};
+
+/// Base URL used for the pages which are not bundled in the docset.
+const DOCS_RS_URL: &str = "https://docs.rs";
+
+/// Return the target of a rustdoc redirect page, or `None` if the page is not a redirect.
+fn redirect_target(contents: &str) -> Option<&str> {
+    if !contents.contains("<title>Redirection</title>") {
+        return None;
+    }
+    let start = contents.find("URL=")? + "URL=".len();
+    let len = contents[start..].find('"')?;
+    Some(&contents[start..start + len])
+}
+
+/// Resolve `target` relatively to `page_dir`, both relative to the documentation root.
+/// Return `None` if the target escapes the documentation root.
+fn resolve_relative(page_dir: &Path, target: &str) -> Option<PathBuf> {
+    let mut resolved = PathBuf::new();
+    for component in page_dir.join(target).components() {
+        match component {
+            Component::Normal(c) => resolved.push(c),
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return None;
+                }
+            }
+            Component::CurDir => {}
+            _ => return None
+        }
+    }
+    Some(resolved)
+}
+
+/// Join the components of a relative path with forward slashes, as expected in URLs.
+fn url_path(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Replace the contents of the file at `path`. The file is recreated rather than truncated, so that
+/// the rustdoc output is left untouched when the docset files are hard links to it.
+fn rewrite(path: &Path, contents: String) -> Result<()> {
+    remove_file(path).context(IoWrite)?;
+    write(path, contents).context(IoWrite)
+}
+
+/// Call `f` on every HTML file located under `dir`, recursively.
+fn for_each_html_file<F: FnMut(&Path) -> Result<()>>(dir: &Path, f: &mut F) -> Result<()> {
+    for dir_entry in read_dir(dir).context(IoRead)? {
+        let path = dir_entry.context(IoRead)?.path();
+        if path.is_dir() {
+            for_each_html_file(&path, f)?;
+        } else if path.extension() == Some(OsStr::new("html")) {
+            f(&path)?;
+        }
+    }
+    Ok(())
+}
+
+fn fix_redirect(docs_root: &Path, path: &Path) -> Result<()> {
+    let contents = read_to_string(path).context(IoRead)?;
+    let target = match redirect_target(&contents) {
+        Some(target) if !target.contains("://") => target,
+        _ => return Ok(())
+    };
+    let page_rel = path.strip_prefix(docs_root).unwrap();
+    let page_dir = page_rel.parent().unwrap_or_else(|| Path::new(""));
+    let target_rel = match resolve_relative(page_dir, target) {
+        Some(target_rel) => target_rel,
+        None => return Ok(())
+    };
+    let (page_crate, target_crate) = match (page_rel.components().next(), target_rel.components().next()) {
+        (Some(page_crate), Some(target_crate)) => (page_crate, target_crate),
+        _ => return Ok(())
+    };
+    if page_crate == target_crate {
+        return Ok(());
+    }
+
+    let new_target = if docs_root.join(&target_rel).is_file() {
+        let up = "../".repeat(page_dir.components().count());
+        format!("{}{}", up, url_path(&target_rel))
+    } else {
+        format!(
+            "{}/{}/latest/{}",
+            DOCS_RS_URL,
+            target_crate.as_os_str().to_string_lossy(),
+            url_path(&target_rel)
+        )
+    };
+    rewrite(path, contents.replace(target, &new_target))
+}
+
+/// Rewrite the rustdoc redirect pages of `docs_root` which point into another crate, so that they
+/// point at the bundled page if it is present in the docset, or at docs.rs otherwise.
+pub fn fix_redirects<P: AsRef<Path>>(docs_root: P) -> Result<()> {
+    let docs_root = docs_root.as_ref();
+    for_each_html_file(docs_root, &mut |path| fix_redirect(docs_root, path))
+}
+
+/// Return the values of all the `href` attributes of an HTML page.
+fn hrefs(contents: &str) -> impl Iterator<Item = &str> {
+    contents.split("href=\"").skip(1).filter_map(|s| s.find('"').map(|end| &s[..end]))
+}
+
+fn collect_linked_pages(
+    docs_root: &Path,
+    path: &Path,
+    crate_dirs: &[String],
+    linked: &mut HashSet<PathBuf>
+) -> Result<()> {
+    let contents = read_to_string(path).context(IoRead)?;
+    let page_dir = path.strip_prefix(docs_root).unwrap().parent().unwrap_or_else(|| Path::new(""));
+    for href in hrefs(&contents) {
+        let href = href.split('#').next().unwrap();
+        if href.is_empty() || href.contains("://") {
+            continue;
+        }
+        if let Some(target) = resolve_relative(page_dir, href) {
+            let target_crate = target.components().next().map(|c| c.as_os_str().to_string_lossy());
+            match target_crate {
+                Some(target_crate) if !crate_dirs.iter().any(|d| *d == target_crate) => {
+                    linked.insert(target);
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Return the pages, relative to `docs_root`, which are linked from the documentation of the
+/// crates in `crate_dirs` but are located outside of these crates' directories.
+pub fn linked_pages<P: AsRef<Path>>(docs_root: P, crate_dirs: &[String]) -> Result<HashSet<PathBuf>> {
+    let docs_root = docs_root.as_ref();
+    let mut linked = HashSet::new();
+    for crate_dir in crate_dirs {
+        let dir = docs_root.join(crate_dir);
+        if dir.is_dir() {
+            for_each_html_file(&dir, &mut |path| collect_linked_pages(docs_root, path, crate_dirs, &mut linked))?;
+        }
+    }
+    Ok(linked)
+}
+
+/// Return the target of the first source link of the page at `path`, which is the link to the
+/// source of the item documented by the page, relative to `docs_root`.
+fn source_link(docs_root: &Path, path: &Path) -> Result<Option<String>> {
+    let contents = read_to_string(path).context(IoRead)?;
+    let document = Html::parse_document(&contents);
+    let source_links = Selector::parse("a.srclink, a.src").unwrap();
+    let href = match document.select(&source_links).find_map(|element| element.value().attr("href")) {
+        Some(href) => href,
+        None => return Ok(None)
+    };
+    let (target, anchor) = match href.find('#') {
+        Some(anchor_start) => href.split_at(anchor_start),
+        None => (href, "")
+    };
+    let page_dir = path.strip_prefix(docs_root).unwrap().parent().unwrap_or_else(|| Path::new(""));
+    Ok(resolve_relative(page_dir, target).map(|target| format!("{}{}", url_path(&target), anchor)))
+}
+
+/// Return the module directory which most likely corresponds to the source file `source`, such as
+/// `mycrate/net` for `src/mycrate/net/mod.rs.html#12`.
+fn source_module_dir(source: &str) -> Vec<&str> {
+    let file = source.split('#').next().unwrap();
+    let mut components = file.split('/').skip(1).collect::<Vec<_>>();
+    if let Some(last) = components.pop() {
+        let module = last.trim_end_matches(".html").trim_end_matches(".rs");
+        if !["lib", "main", "mod"].contains(&module) {
+            components.push(module);
+        }
+    }
+    components
+}
+
+/// Return the item pages, relative to `docs_root`, which document an item re-exported from its
+/// defining module. Such pages are identified by their file name and their link to the source of
+/// the item. For each item, the page located closest to the module of the source file is
+/// considered canonical and the others are returned.
+pub fn reexport_duplicates<P: AsRef<Path>>(docs_root: P, skip_dirs: &[String]) -> Result<HashSet<PathBuf>> {
+    let docs_root = docs_root.as_ref();
+    let mut pages_by_name = HashMap::<String, Vec<PathBuf>>::new();
+    for dir_entry in read_dir(docs_root).context(IoRead)? {
+        let dir = dir_entry.context(IoRead)?.path();
+        let is_skipped = skip_dirs.iter().any(|d| dir.file_name() == Some(OsStr::new(d)));
+        if !dir.is_dir() || is_skipped {
+            continue;
+        }
+        for_each_html_file(&dir, &mut |path| {
+            let file_name = path.file_name().unwrap().to_string_lossy();
+            // Item pages are named `<type>.<name>.html`.
+            if file_name.split('.').count() == 3 {
+                let page = path.strip_prefix(docs_root).unwrap().to_owned();
+                pages_by_name.entry(file_name.into_owned()).or_default().push(page);
+            }
+            Ok(())
+        })?;
+    }
+
+    let mut duplicates = HashSet::new();
+    for pages in pages_by_name.values().filter(|pages| pages.len() > 1) {
+        let mut pages_by_source = HashMap::<String, Vec<&PathBuf>>::new();
+        for page in pages {
+            if let Some(source) = source_link(docs_root, &docs_root.join(page))? {
+                pages_by_source.entry(source).or_default().push(page);
+            }
+        }
+        for (source, mut pages) in pages_by_source.into_iter().filter(|(_, pages)| pages.len() > 1) {
+            let module_dir = source_module_dir(&source);
+            pages.sort_by_key(|page| {
+                let page_dir = page.parent().unwrap_or_else(|| Path::new(""));
+                let common = page_dir
+                    .components()
+                    .zip(&module_dir)
+                    .take_while(|(c, m)| c.as_os_str() == OsStr::new(m))
+                    .count();
+                (Reverse(common), page.components().count(), page.to_owned())
+            });
+            duplicates.extend(pages.into_iter().skip(1).cloned());
+        }
+    }
+    Ok(duplicates)
+}
+
+/// Return the type of the member documented at the anchor `id`, along with the member's name.
+fn member_anchor(id: &str) -> Option<(EntryType, &str)> {
+    let dot = id.find('.')?;
+    let (kind, name) = (&id[..dot], &id[dot + 1..]);
+    // Nested anchors, such as the fields of enum variants, are not indexed.
+    if name.is_empty() || name.contains('.') {
+        return None;
+    }
+    // Anchors which appear in several impl blocks are suffixed with `-N` by rustdoc.
+    let name = name.split('-').next().unwrap();
+    let ty = match kind {
+        "associatedconstant" => EntryType::AssociatedConstant,
+        "associatedtype" => EntryType::AssociatedType,
+        "method" | "tymethod" => EntryType::Method,
+        "structfield" => EntryType::Field,
+        "variant" => EntryType::Variant,
+        _ => return None
+    };
+    Some((ty, name))
+}
+
+/// Return how the character `c`, at offset `i` of `s`, changes the angle bracket nesting depth.
+fn generic_depth_change(s: &str, i: usize, c: char) -> i32 {
+    match c {
+        '<' => 1,
+        // Closure bounds, such as `F: Fn() -> T`, contain arrows.
+        '>' if !s[..i].ends_with('-') => -1,
+        _ => 0
+    }
+}
+
+/// Return the length of the generic parameters at the start of `s`, including the angle brackets.
+fn generics_len(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        depth += generic_depth_change(s, i, c);
+        if depth == 0 {
+            return Some(i + c.len_utf8());
+        }
+    }
+    None
+}
+
+/// Return the offset of the first occurrence of `pattern` in `s` which is not inside angle brackets.
+fn find_top_level(s: &str, pattern: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        if depth == 0 && s[i..].starts_with(pattern) {
+            return Some(i);
+        }
+        depth += generic_depth_change(s, i, c);
+    }
+    None
+}
+
+/// Return the trait implemented by the impl block with the header `header`, such as `From<T>` for
+/// `impl<T> From<T> for Foo<T> where ...`, or `None` for an inherent impl block.
+fn impl_trait_name(header: &str) -> Option<String> {
+    let header = header.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut rest = header.trim_start_matches("unsafe ").strip_prefix("impl")?;
+    if rest.starts_with('<') {
+        rest = &rest[generics_len(rest)?..];
+    }
+    let trait_name = rest[..find_top_level(rest, " for ")?].trim();
+    if trait_name.is_empty() {
+        None
+    } else {
+        Some(trait_name.to_owned())
+    }
+}
+
+/// Parse the page of `page_entry`, located at `file_path`, and return entries for the members it
+/// documents. The methods and associated items of trait implementations are not indexed, only
+/// those of inherent impl blocks, and of the trait itself on trait pages, so that each associated
+/// item is only indexed once, under the type or trait defining it.
+pub fn member_entries(page_entry: &DocsetEntry, file_path: &Path, separator: &str) -> Result<Vec<DocsetEntry>> {
+    let contents = read_to_string(file_path).context(IoRead)?;
+    let document = Html::parse_document(&contents);
+    let all_anchors = Selector::parse("[id]").unwrap();
+    let inherent_anchors = Selector::parse("#implementations-list [id]").unwrap();
+    let inherent_ids = document
+        .select(&inherent_anchors)
+        .filter_map(|element| element.value().id())
+        .collect::<HashSet<_>>();
+
+    let mut seen = HashSet::new();
+    let mut entries = vec![];
+    for id in document.select(&all_anchors).filter_map(|element| element.value().id()) {
+        let (ty, name) = match member_anchor(id) {
+            Some(member) => member,
+            None => continue
+        };
+        if ty.is_associated_item() && page_entry.ty != EntryType::Trait && !inherent_ids.contains(id) {
+            continue;
+        }
+        if !seen.insert((ty.to_string(), name)) {
+            continue;
+        }
+        entries.push(DocsetEntry::new(
+            format!("{}{}{}", page_entry.name, separator, name),
+            ty,
+            PathBuf::from(format!("{}#{}", url_path(&page_entry.path), id))
+        ));
+    }
+
+    // The trait implementations are indexed under the implementing type, not under the trait.
+    if page_entry.ty != EntryType::Trait {
+        let mut implemented = HashSet::new();
+        let impl_headers = Selector::parse("section.impl[id^=\"impl-\"] > .code-header").unwrap();
+        for header in document.select(&impl_headers) {
+            let id = header.parent().and_then(|section| section.value().as_element()).and_then(|section| section.id());
+            let trait_name = impl_trait_name(&header.text().collect::<String>());
+            if let (Some(id), Some(trait_name)) = (id, trait_name) {
+                let name = format!("{}: {}", page_entry.name, trait_name);
+                if implemented.insert(trait_name) {
+                    entries.push(DocsetEntry::new(
+                        name,
+                        EntryType::Instance,
+                        PathBuf::from(format!("{}#{}", url_path(&page_entry.path), id))
+                    ));
+                }
+            }
+        }
+    }
+
+    // Only the top-level headings are indexed, such as the implementations sections and the
+    // sections of the documentation, the nested ones are mostly noise.
+    let headings = Selector::parse("h2[id]").unwrap();
+    for heading in document.select(&headings) {
+        let id = heading.value().id().unwrap();
+        let title = heading.text().collect::<String>();
+        // Newer rustdoc versions append a `§` anchor link to the headings.
+        let title = title.trim().trim_end_matches('§').trim();
+        if title.is_empty() {
+            continue;
+        }
+        entries.push(DocsetEntry::new(
+            format!("{} \u{2014} {}", page_entry.name, title),
+            EntryType::Section,
+            PathBuf::from(format!("{}#{}", url_path(&page_entry.path), id))
+        ));
+    }
+    Ok(entries)
+}
+
+/// Insert a Dash table of contents anchor before each member documented in the page at `path`.
+fn add_page_toc_anchors(path: &Path) -> Result<()> {
+    let file_name = path.file_name().unwrap().to_string_lossy();
+    if !["enum.", "struct.", "trait.", "union."].iter().any(|prefix| file_name.starts_with(prefix)) {
+        return Ok(());
+    }
+    let contents = read_to_string(path).context(IoRead)?;
+    let mut anchored = String::with_capacity(contents.len());
+    let mut copied = 0;
+    let mut search_from = 0;
+    while let Some(offset) = contents[search_from..].find(" id=\"") {
+        let id_start = search_from + offset + " id=\"".len();
+        let id_len = match contents[id_start..].find('"') {
+            Some(id_len) => id_len,
+            None => break
+        };
+        search_from = id_start + id_len;
+        let (ty, name) = match member_anchor(&contents[id_start..id_start + id_len]) {
+            Some(member) => member,
+            None => continue
+        };
+        if let Some(tag_start) = contents[copied..id_start].rfind('<') {
+            let tag_start = copied + tag_start;
+            anchored.push_str(&contents[copied..tag_start]);
+            anchored.push_str(&format!(
+                "<a name=\"//apple_ref/cpp/{}/{}\" class=\"dashAnchor\"></a>",
+                ty, name
+            ));
+            copied = tag_start;
+        }
+    }
+    anchored.push_str(&contents[copied..]);
+    rewrite(path, anchored)
+}
+
+/// Insert Dash table of contents anchors in the type and trait pages located under `docs_root`.
+pub fn add_toc_anchors<P: AsRef<Path>>(docs_root: P) -> Result<()> {
+    for_each_html_file(docs_root.as_ref(), &mut add_page_toc_anchors)
+}
+
+/// Return the title and the text content of the page at `path`, stripped of markup, for the
+/// full-text search index. Only the main content is kept, without the sidebar and the search form.
+pub fn page_text(path: &Path) -> Result<(String, String)> {
+    let contents = read_to_string(path).context(IoRead)?;
+    let document = Html::parse_document(&contents);
+    let title = Selector::parse("title").unwrap();
+    let title = document
+        .select(&title)
+        .next()
+        .map(|title| title.text().collect::<String>())
+        .unwrap_or_default();
+    // Older rustdoc versions name the main content `main`.
+    let main = ["#main-content", "#main", "body"]
+        .iter()
+        .find_map(|selector| document.select(&Selector::parse(selector).unwrap()).next());
+    let text = main
+        .map(|main| main.text().flat_map(str::split_whitespace).collect::<Vec<_>>().join(" "))
+        .unwrap_or_default();
+    Ok((title, text))
+}
+
+/// Extensions of the shared rustdoc assets, such as the style sheets, scripts and fonts.
+const ASSET_EXTENSIONS: &[&str] = &["css", "js", "woff", "woff2", "ttf", "svg", "png", "ico"];
+
+/// Root directories of the shared rustdoc assets, depending on the rustdoc version.
+const STATIC_DIRS: &[&str] = &["static.files", "static"];
+
+/// Return the link `target` of the page located in `page_dir` rewritten to resolve inside
+/// `docs_root`, or `None` if it already does or cannot be resolved there. Root-relative links are
+/// made relative, and the shared assets which the link misses are looked up at the documentation
+/// root and in its static directories.
+fn localized_link(docs_root: &Path, page_dir: &Path, target: &str) -> Option<String> {
+    if target.is_empty() || target.starts_with('#') || target.starts_with("//") || target.contains(':') {
+        return None;
+    }
+    let path_len = target.find(|c| c == '#' || c == '?').unwrap_or(target.len());
+    let (path, suffix) = target.split_at(path_len);
+    let up = "../".repeat(page_dir.components().count());
+    if path.starts_with('/') {
+        let root_relative = path.trim_start_matches('/');
+        return if docs_root.join(root_relative).exists() {
+            Some(format!("{}{}{}", up, root_relative, suffix))
+        } else {
+            None
+        };
+    }
+    // The links between pages are left to the other passes, such as the redirect fixes.
+    let asset = Path::new(path);
+    if !asset.extension().map_or(false, |extension| ASSET_EXTENSIONS.iter().any(|e| OsStr::new(e) == extension)) {
+        return None;
+    }
+    if resolve_relative(page_dir, path).map_or(false, |resolved| docs_root.join(resolved).is_file()) {
+        return None;
+    }
+    let file_name = asset.file_name()?.to_string_lossy();
+    // Keep the path below the static directory, assets are nested there in some rustdoc versions.
+    let static_path = STATIC_DIRS.iter().find_map(|dir| {
+        let start = path.find(&format!("{}/", dir))?;
+        Some(path[start..].to_owned())
+    });
+    let in_static_dirs = STATIC_DIRS.iter().map(|dir| format!("{}/{}", dir, file_name));
+    static_path
+        .into_iter()
+        .chain(Some(file_name.to_string()))
+        .chain(in_static_dirs)
+        .find(|candidate| docs_root.join(candidate).is_file())
+        .map(|candidate| format!("{}{}{}", up, candidate, suffix))
+}
+
+fn fix_page_links(docs_root: &Path, path: &Path) -> Result<()> {
+    let contents = read_to_string(path).context(IoRead)?;
+    let page_dir = path.strip_prefix(docs_root).unwrap().parent().unwrap_or_else(|| Path::new(""));
+    let mut fixed = String::with_capacity(contents.len());
+    let mut copied = 0;
+    let mut search_from = 0;
+    let next_link = |from: usize| {
+        [" href=\"", " src=\""]
+            .iter()
+            .filter_map(|attribute| contents[from..].find(attribute).map(|offset| from + offset + attribute.len()))
+            .min()
+    };
+    while let Some(start) = next_link(search_from) {
+        let len = match contents[start..].find('"') {
+            Some(len) => len,
+            None => break
+        };
+        search_from = start + len;
+        if let Some(link) = localized_link(docs_root, page_dir, &contents[start..start + len]) {
+            fixed.push_str(&contents[copied..start]);
+            fixed.push_str(&link);
+            copied = start + len;
+        }
+    }
+    if copied == 0 {
+        return Ok(());
+    }
+    fixed.push_str(&contents[copied..]);
+    rewrite(path, fixed)
+}
+
+/// Rewrite the links of the pages of `docs_root` to the shared assets and pages which do not
+/// resolve inside `docs_root`, such as root-relative links, so that the docset is self-contained.
+pub fn fix_links<P: AsRef<Path>>(docs_root: P) -> Result<()> {
+    let docs_root = docs_root.as_ref();
+    for_each_html_file(docs_root, &mut |path| fix_page_links(docs_root, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        env,
+        fs::{create_dir_all, remove_dir_all},
+        process
+    };
+
+    /// Return an empty temporary directory named after `name`.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("cargo-docset-html-{}-{}", name, process::id()));
+        if dir.exists() {
+            remove_dir_all(&dir).unwrap();
+        }
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Write the page at `path`, relative to `root`.
+    fn write_page(root: &Path, path: &str, contents: &str) {
+        let path = root.join(path);
+        create_dir_all(path.parent().unwrap()).unwrap();
+        write(path, contents).unwrap();
+    }
+
+    /// Contents of a rustdoc redirect page to `target`.
+    fn redirect_page(target: &str) -> String {
+        format!(
+            "<!DOCTYPE html><html><head><meta http-equiv=\"refresh\" content=\"0;URL={0}\">\
+             <title>Redirection</title></head><body><p>Redirecting to <a href=\"{0}\">{0}</a>...</p></body></html>",
+            target
+        )
+    }
+
+    const STRUCT_PAGE: &str = r##"<html><body>
+<h2 id="fields">Fields</h2>
+<span id="structfield.size"></span>
+<span id="structfield.inner.field"></span>
+<h2 id="implementations">Implementations</h2>
+<div id="implementations-list"><section id="method.new"></section><section id="method.new-1"></section></div>
+<h2 id="trait-implementations">Trait Implementations <a href="#trait-implementations">§</a></h2>
+<div id="trait-implementations-list">
+<section id="impl-Clone-for-Point" class="impl"><h3 class="code-header">impl Clone for Point</h3></section>
+<section id="method.clone"></section>
+<section id="impl-From%3CT%3E-for-Point" class="impl"><h3 class="code-header">impl&lt;T: Into&lt;u32&gt;&gt; From&lt;T&gt; for Point</h3></section>
+</div>
+</body></html>"##;
+
+    const TRAIT_PAGE: &str = r##"<html><body>
+<h2 id="required-methods">Required Methods</h2>
+<section id="tymethod.area"></section>
+<section id="associatedtype.Unit"></section>
+<h2 id="implementors">Implementors</h2>
+<section id="impl-Shape-for-Point" class="impl"><h3 class="code-header">impl Shape for Point</h3></section>
+</body></html>"##;
+
+    /// Entry of the `geo::Point` struct page.
+    fn point() -> DocsetEntry {
+        DocsetEntry::new("geo::Point".to_owned(), EntryType::Struct, "geo/struct.Point.html".into())
+    }
+
+    /// Entry of the `geo::Shape` trait page.
+    fn shape() -> DocsetEntry {
+        DocsetEntry::new("geo::Shape".to_owned(), EntryType::Trait, "geo/trait.Shape.html".into())
+    }
+
+    /// Return the member entries of the page of `page_entry`, written to `dir` with `contents`,
+    /// which are of one of the types `types`.
+    fn page_members(dir: &Path, page_entry: &DocsetEntry, contents: &str, types: &[EntryType]) -> Vec<DocsetEntry> {
+        write_page(dir, &url_path(&page_entry.path), contents);
+        let entries = member_entries(page_entry, &dir.join(&page_entry.path), "::").unwrap();
+        entries.into_iter().filter(|entry| types.contains(&entry.ty)).collect()
+    }
+
+    /// Return an entry of type `ty` named `name`, at the anchor `anchor` of the page at `path`.
+    fn member(name: &str, ty: EntryType, path: &str, anchor: &str) -> DocsetEntry {
+        DocsetEntry::new(name.to_owned(), ty, PathBuf::from(format!("{}#{}", path, anchor)))
+    }
+
+    #[test]
+    fn member_entries_of_struct_page() {
+        let dir = temp_dir("member-entries-struct");
+        let members = page_members(&dir, &point(), STRUCT_PAGE, &[EntryType::Field, EntryType::Method]);
+        let path = "geo/struct.Point.html";
+        assert_eq!(members, vec![
+            member("geo::Point::size", EntryType::Field, path, "structfield.size"),
+            member("geo::Point::new", EntryType::Method, path, "method.new")
+        ]);
+    }
+
+    #[test]
+    fn member_entries_of_trait_page() {
+        let dir = temp_dir("member-entries-trait");
+        let members = page_members(&dir, &shape(), TRAIT_PAGE, &[EntryType::Method, EntryType::AssociatedType]);
+        let path = "geo/trait.Shape.html";
+        assert_eq!(members, vec![
+            member("geo::Shape::area", EntryType::Method, path, "tymethod.area"),
+            member("geo::Shape::Unit", EntryType::AssociatedType, path, "associatedtype.Unit")
+        ]);
+    }
+
+    #[test]
+    fn member_entries_of_trait_implementations() {
+        let dir = temp_dir("member-entries-impls");
+        let members = page_members(&dir, &point(), STRUCT_PAGE, &[EntryType::Instance, EntryType::Method]);
+        let path = "geo/struct.Point.html";
+        // The methods of the trait implementations are only indexed under the trait.
+        assert_eq!(members, vec![
+            member("geo::Point::new", EntryType::Method, path, "method.new"),
+            member("geo::Point: Clone", EntryType::Instance, path, "impl-Clone-for-Point"),
+            member("geo::Point: From<T>", EntryType::Instance, path, "impl-From%3CT%3E-for-Point")
+        ]);
+        // The implementors of a trait are indexed under their own type only.
+        assert!(page_members(&dir, &shape(), TRAIT_PAGE, &[EntryType::Instance]).is_empty());
+    }
+
+    #[test]
+    fn member_entries_of_sections() {
+        let dir = temp_dir("member-entries-sections");
+        let members = page_members(&dir, &point(), STRUCT_PAGE, &[EntryType::Section]);
+        let path = "geo/struct.Point.html";
+        // The trailing anchor links of the headings are not part of their title.
+        assert_eq!(members, vec![
+            member("geo::Point \u{2014} Fields", EntryType::Section, path, "fields"),
+            member("geo::Point \u{2014} Implementations", EntryType::Section, path, "implementations"),
+            member("geo::Point \u{2014} Trait Implementations", EntryType::Section, path, "trait-implementations")
+        ]);
+        let members = page_members(&dir, &shape(), TRAIT_PAGE, &[EntryType::Section]);
+        let path = "geo/trait.Shape.html";
+        assert_eq!(members, vec![
+            member("geo::Shape \u{2014} Required Methods", EntryType::Section, path, "required-methods"),
+            member("geo::Shape \u{2014} Implementors", EntryType::Section, path, "implementors")
+        ]);
+    }
+
+    #[test]
+    fn member_entries_of_union_page() {
+        let dir = temp_dir("member-entries-union");
+        let bits = DocsetEntry::new("geo::Bits".to_owned(), EntryType::Union, "geo/union.Bits.html".into());
+        let contents = r##"<html><body>
+<h2 id="fields">Fields</h2>
+<span id="structfield.word"></span>
+<span id="structfield.bytes"></span>
+</body></html>"##;
+        let members = page_members(&dir, &bits, contents, &[EntryType::Field]);
+        let path = "geo/union.Bits.html";
+        assert_eq!(members, vec![
+            member("geo::Bits::word", EntryType::Field, path, "structfield.word"),
+            member("geo::Bits::bytes", EntryType::Field, path, "structfield.bytes")
+        ]);
+    }
+
+    #[test]
+    fn fix_links_of_nested_pages() {
+        let dir = temp_dir("fix-links");
+        write_page(&dir, "static.files/rustdoc.css", "");
+        write_page(&dir, "static.files/main.js", "");
+        write_page(&dir, "geo/index.html", "<html></html>");
+        let page = |css: &str, index: &str| {
+            format!(
+                "<html><head><link rel=\"stylesheet\" href=\"{}\"><script src=\"../../static.files/main.js\"></script>\
+                 </head><body><a href=\"{}\">geo</a><a href=\"https://docs.rs/geo\">docs.rs</a></body></html>",
+                css, index
+            )
+        };
+        write_page(&dir, "geo/shapes/struct.Line.html", &page("../../rustdoc.css", "/geo/index.html#top"));
+        fix_links(&dir).unwrap();
+        // The missing assets are found in the static directory, the root-relative links are made
+        // relative, and the links which already resolve are left untouched.
+        assert_eq!(
+            read_to_string(dir.join("geo/shapes/struct.Line.html")).unwrap(),
+            page("../../static.files/rustdoc.css", "../../geo/index.html#top")
+        );
+    }
+
+    #[test]
+    fn fix_redirects_rewrites_cross_crate_redirects() {
+        let dir = temp_dir("fix-redirects");
+        write_page(&dir, "geo/struct.Point.html", &redirect_page("./../geo_core/struct.Point.html"));
+        write_page(&dir, "geo_core/struct.Point.html", "<html></html>");
+        write_page(&dir, "geo/shapes/struct.Line.html", &redirect_page("../../lines/struct.Line.html"));
+        write_page(&dir, "geo/struct.Local.html", &redirect_page("inner/struct.Local.html"));
+        write_page(&dir, "geo/struct.Online.html", &redirect_page("https://example.com/struct.Online.html"));
+        fix_redirects(&dir).unwrap();
+
+        let page = |path: &str| read_to_string(dir.join(path)).unwrap();
+        assert_eq!(page("geo/struct.Point.html"), redirect_page("../geo_core/struct.Point.html"));
+        assert_eq!(
+            page("geo/shapes/struct.Line.html"),
+            redirect_page("https://docs.rs/lines/latest/lines/struct.Line.html")
+        );
+        assert_eq!(page("geo/struct.Local.html"), redirect_page("inner/struct.Local.html"));
+        assert_eq!(page("geo/struct.Online.html"), redirect_page("https://example.com/struct.Online.html"));
+        assert_eq!(page("geo_core/struct.Point.html"), "<html></html>");
+    }
+}
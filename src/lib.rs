@@ -0,0 +1,21 @@
+//! Generation of Zeal/Dash docsets from the rustdoc output of a cargo workspace.
+//!
+//! This is the library behind the `cargo docset` command, for tools which want to generate docsets
+//! without running it.
+
+mod commands;
+mod common;
+pub mod error;
+mod html;
+
+pub use commands::clean::clean_docsets;
+pub use commands::generate::{
+    generate, generate_from_doc_dir, generate_split, generate_with_hook, EntryHook, EntryTypeCounts, GenerateConfig,
+    GeneratedDocset
+};
+pub use commands::list::list_entries;
+pub use commands::merge::merge_docsets;
+pub use commands::verify::verify_docset;
+pub use commands::watch::watch;
+pub use common::{DocsetEntry, EntryType, Package};
+pub use error::{Error, Result};
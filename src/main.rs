@@ -1,57 +1,209 @@
 use cargo::{
     core::Workspace, util::important_paths::find_root_manifest_for_wd, Config as CargoCfg
 };
-use clap::{crate_authors, crate_version, App, ArgMatches, Arg, SubCommand};
+use clap::{crate_authors, crate_version, App, ArgMatches, Arg, ErrorKind, SubCommand};
 use snafu::ResultExt;
 
-use std::env::current_dir;
+use std::{env::current_dir, path::{Path, PathBuf}};
 
-mod commands;
-mod common;
-mod error;
-
-use crate::error::*;
-use commands::generate::{generate, GenerateConfig};
-use common::Package;
+use cargo_docset::{
+    clean_docsets, error::*, generate, generate_from_doc_dir, generate_split, list_entries, merge_docsets,
+    verify_docset, watch, EntryType, GenerateConfig, Package
+};
 
 use std::process::exit;
 
-fn run(sub_matches: &ArgMatches) -> Result<()> {
+/// Create the cargo configuration according to the output and network options of `sub_matches`.
+fn cargo_config(sub_matches: &ArgMatches) -> Result<CargoCfg> {
     let quiet = sub_matches.is_present("quiet");
     let verbosity_level = sub_matches.occurrences_of("verbose") as u32;
 
     if quiet && verbosity_level != 0 {
         eprintln!("Error: cannot specify `--quiet` with `--verbose`.");
-        exit(1);
+        exit(2);
     }
 
+    // Like many tools, honor NO_COLOR unless colors are requested explicitly.
+    let color = match sub_matches.value_of("color") {
+        None | Some("auto") if std::env::var_os("NO_COLOR").map_or(false, |v| !v.is_empty()) => {
+            Some("never".to_owned())
+        }
+        color => color.map(ToOwned::to_owned)
+    };
+
+    // RUST_LOG takes precedence, the extra verbosity levels reveal the indexing decisions.
+    let log_filter = match verbosity_level {
+        0 | 1 => "cargo_docset=info",
+        2 => "cargo_docset=debug",
+        _ => "cargo_docset=trace"
+    };
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(log_filter)).init();
+
     let mut cargo_cfg = CargoCfg::default().context(CargoConfig)?;
     cargo_cfg.configure(
         verbosity_level,
         Some(quiet),
-        &None,
+        &color,
         sub_matches.is_present("frozen"),
         sub_matches.is_present("locked"),
         sub_matches.is_present("offline"),
         &None,
         &[]
     ).context(CargoConfig)?;
+    Ok(cargo_cfg)
+}
 
-    let mut cfg = GenerateConfig::default();
-    cfg.no_dependencies = sub_matches.is_present("no-deps");
-    cfg.package = if sub_matches.is_present("all") {
-        Package::All
-    } else if let Some(packages) = sub_matches.values_of_lossy("package") {
-        Package::List(packages)
-    } else if let Some(package) = sub_matches.value_of("package") {
-        Package::Single(package.to_owned())
+/// Return the path of the root manifest of the workspace selected by `--manifest-path`, or of the
+/// workspace of the current directory.
+fn root_manifest(sub_matches: &ArgMatches) -> Result<PathBuf> {
+    let cur_dir = current_dir().context(Cwd)?;
+    if let Some(manifest_path) = sub_matches.value_of_os("manifest-path") {
+        let manifest_path = cur_dir.join(manifest_path);
+        if !manifest_path.ends_with("Cargo.toml") || !manifest_path.is_file() {
+            return Args {
+                msg: "--manifest-path must be the path to an existing Cargo.toml"
+            }
+            .fail();
+        }
+        Ok(manifest_path)
     } else {
-        Package::Current
+        find_root_manifest_for_wd(&cur_dir).context(CargoConfig)
+    }
+}
+
+fn run_clean(sub_matches: &ArgMatches) -> Result<()> {
+    let cargo_cfg = cargo_config(sub_matches)?;
+    let workspace = Workspace::new(&root_manifest(sub_matches)?, &cargo_cfg).context(CargoConfig)?;
+    let output_dir = sub_matches.value_of_os("output").map(PathBuf::from);
+    clean_docsets(&cargo_cfg, &workspace, output_dir.as_deref(), sub_matches.value_of("docset-name"))
+}
+
+/// Parse the entry kinds given with the `arg` option.
+fn entry_types(sub_matches: &ArgMatches, arg: &str) -> Result<Vec<EntryType>> {
+    let kinds = match sub_matches.values_of(arg) {
+        Some(kinds) => kinds,
+        None => return Ok(vec![])
+    };
+    kinds.map(EntryType::from_kind).collect::<Option<_>>().ok_or(Error::Args {
+        msg: "entry kinds must be one of associated-constant, associated-type, attribute, constant, derive, enum, \
+              field, function, impl, macro, method, module, package, primitive, section, static, struct, trait, type, \
+              union or variant"
+    })
+}
+
+fn run_list(sub_matches: &ArgMatches) -> Result<()> {
+    let cargo_cfg = cargo_config(sub_matches)?;
+    let workspace = Workspace::new(&root_manifest(sub_matches)?, &cargo_cfg).context(CargoConfig)?;
+    let cfg = GenerateConfig {
+        package: if sub_matches.is_present("all") {
+            Package::All
+        } else if let Some(packages) = sub_matches.values_of_lossy("package") {
+            Package::List(packages)
+        } else {
+            Package::Current
+        },
+        index_members: sub_matches.is_present("index-members"),
+        include_paths: sub_matches.values_of_lossy("path").unwrap_or_default(),
+        ..GenerateConfig::default()
+    };
+    let types = entry_types(sub_matches, "type")?;
+    for entry in list_entries(&cargo_cfg, &workspace, cfg, &types)? {
+        println!("{}\t{}\t{}", entry.name, entry.ty, entry.path.display());
+    }
+    Ok(())
+}
+
+fn run_verify(sub_matches: &ArgMatches) -> Result<()> {
+    let cargo_cfg = cargo_config(sub_matches)?;
+    verify_docset(&cargo_cfg, Path::new(sub_matches.value_of_os("PATH").unwrap()))
+}
+
+fn run_merge(sub_matches: &ArgMatches) -> Result<()> {
+    let cargo_cfg = cargo_config(sub_matches)?;
+    let docsets: Vec<PathBuf> = sub_matches.values_of_os("DOCSET").unwrap().map(PathBuf::from).collect();
+    merge_docsets(&cargo_cfg, Path::new(sub_matches.value_of_os("output").unwrap()), &docsets)?;
+    Ok(())
+}
+
+fn run(sub_matches: &ArgMatches) -> Result<()> {
+    if let Some(clean_matches) = sub_matches.subcommand_matches("clean") {
+        return run_clean(clean_matches);
+    }
+    if let Some(list_matches) = sub_matches.subcommand_matches("list") {
+        return run_list(list_matches);
+    }
+    if let Some(verify_matches) = sub_matches.subcommand_matches("verify") {
+        return run_verify(verify_matches);
+    }
+    if let Some(merge_matches) = sub_matches.subcommand_matches("merge") {
+        return run_merge(merge_matches);
+    }
+    let cargo_cfg = cargo_config(sub_matches)?;
+
+    let mut cfg = GenerateConfig {
+        no_dependencies: sub_matches.is_present("no-deps"),
+        package: if sub_matches.is_present("all") {
+            Package::All
+        } else if let Some(packages) = sub_matches.values_of_lossy("package") {
+            Package::List(packages)
+        } else if let Some(package) = sub_matches.value_of("package") {
+            Package::Single(package.to_owned())
+        } else {
+            Package::Current
+        },
+        doc_private_items: sub_matches.is_present("document-private-items"),
+        exclude: sub_matches
+            .values_of_lossy("exclude")
+            .unwrap_or_default(),
+        strict: sub_matches.is_present("strict"),
+        fast_index: sub_matches.is_present("fast-index"),
+        check_config: sub_matches.is_present("check-config"),
+        fix_redirects: sub_matches.is_present("fix-redirects"),
+        primary_only: sub_matches.is_present("primary-only"),
+        post_hook: sub_matches.value_of("post-hook").map(ToOwned::to_owned),
+        linked_deps: sub_matches.is_present("linked-deps"),
+        provenance: sub_matches.is_present("provenance"),
+        versioned: sub_matches.is_present("versioned"),
+        index_members: sub_matches.is_present("index-members"),
+        toc: sub_matches.is_present("toc"),
+        online_redirect_url: sub_matches.value_of("online-redirect-url").map(ToOwned::to_owned),
+        icon: sub_matches.value_of_os("icon").map(PathBuf::from),
+        icon_2x: sub_matches.value_of_os("icon-2x").map(PathBuf::from),
+        feed: sub_matches.value_of("feed").map(ToOwned::to_owned),
+        zeal: sub_matches.is_present("zeal"),
+        link: sub_matches.is_present("link"),
+        incremental: sub_matches.is_present("incremental"),
+        no_build: sub_matches.is_present("no-build") || sub_matches.is_present("no-doc"),
+        compress: sub_matches.is_present("compress"),
+        dump_index: sub_matches.value_of_os("dump-index").map(PathBuf::from),
+        dry_run: sub_matches.is_present("dry-run"),
+        rustdoc_args: sub_matches.values_of_lossy("rustdoc-arg").unwrap_or_default(),
+        docs_rs: sub_matches.is_present("docs-rs"),
+        use_docs_rs_metadata: sub_matches.is_present("use-docs-rs-metadata"),
+        platform_family: sub_matches.value_of("platform-family").map(ToOwned::to_owned),
+        bundle_identifier: sub_matches.value_of("bundle-identifier").map(ToOwned::to_owned),
+        open_after: sub_matches.is_present("open"),
+        split: sub_matches.is_present("split"),
+        theme: sub_matches.value_of("theme").map(ToOwned::to_owned),
+        include_paths: sub_matches.values_of_lossy("include-path").unwrap_or_default(),
+        exclude_paths: sub_matches.values_of_lossy("exclude-path").unwrap_or_default(),
+        no_source: sub_matches.is_present("no-source"),
+        keep_reexports: sub_matches.is_present("keep-reexports"),
+        doc_dir: sub_matches.value_of_os("doc-dir").map(PathBuf::from),
+        enable_js: sub_matches.is_present("enable-js"),
+        keyword: sub_matches.value_of("keyword").map(ToOwned::to_owned),
+        fts: sub_matches.is_present("fts"),
+        timings: sub_matches.is_present("timings"),
+        manifest: sub_matches.is_present("manifest"),
+        temp_dir: sub_matches.value_of_os("temp-dir").map(PathBuf::from),
+        only_deps: sub_matches.values_of_lossy("only-deps").unwrap_or_default(),
+        short_aliases: sub_matches.is_present("short-aliases"),
+        plist_template: sub_matches.value_of_os("plist-template").map(PathBuf::from),
+        output_dir: sub_matches.value_of_os("output").map(PathBuf::from),
+        docset_name: sub_matches.value_of("docset-name").map(ToOwned::to_owned),
+        target: sub_matches.value_of("target").map(ToOwned::to_owned),
+        ..GenerateConfig::default()
     };
-    cfg.doc_private_items = sub_matches.is_present("document-private-items");
-    cfg.exclude = sub_matches
-        .values_of_lossy("exclude")
-        .unwrap_or_else(Vec::new);
     if sub_matches.is_present("all-features") {
         cfg.all_features = true;
     }
@@ -61,6 +213,28 @@ fn run(sub_matches: &ArgMatches) -> Result<()> {
     if sub_matches.is_present("features") {
         cfg.features = sub_matches.values_of_lossy("features").unwrap();
     }
+    if let Some(skip_dirs) = sub_matches.values_of_lossy("skip-dir") {
+        cfg.skip_dirs = skip_dirs;
+    }
+    if let Some(separator) = sub_matches.value_of("name-separator") {
+        cfg.name_separator = separator.to_owned();
+    }
+    if let Some(limit) = sub_matches.value_of("entry-limit") {
+        cfg.entry_limit = Some(limit.parse().map_err(|_| Error::Args {
+            msg: "--entry-limit must be a positive integer"
+        })?);
+    }
+    if let Some(jobs) = sub_matches.value_of("jobs") {
+        cfg.jobs = Some(jobs.parse().map_err(|_| Error::Args {
+            msg: "--jobs must be a positive integer"
+        })?);
+    }
+    cfg.exclude_types = entry_types(sub_matches, "exclude-type")?;
+    for extern_url in sub_matches.values_of("extern-url").into_iter().flatten() {
+        let separator = extern_url.find('=').ok_or(Error::Args { msg: "--extern-url must be given as CRATE=URL" })?;
+        let (crate_name, url) = (&extern_url[..separator], &extern_url[separator + 1..]);
+        cfg.extern_urls.push((crate_name.to_owned(), url.to_owned()));
+    }
     if sub_matches.is_present("no-clean") {
         cfg.clean = false;
     }
@@ -73,12 +247,47 @@ fn run(sub_matches: &ArgMatches) -> Result<()> {
     else if sub_matches.is_present("bin") {
         cfg.bins = sub_matches.values_of_lossy("bin");
     }
+    if sub_matches.is_present("examples") {
+        cfg.examples = Some(vec![])
+    }
+    else if sub_matches.is_present("example") {
+        cfg.examples = sub_matches.values_of_lossy("example");
+    }
 
-    let cur_dir = current_dir().context(Cwd)?;
-    let root_manifest = find_root_manifest_for_wd(&cur_dir).context(CargoConfig)?;
+    // Packaging an existing documentation directory does not involve any cargo workspace.
+    if cfg.doc_dir.is_some() {
+        return generate_from_doc_dir(&cargo_cfg, cfg, None).map(|_| ());
+    }
+
+    let root_manifest = root_manifest(sub_matches)?;
+    if sub_matches.is_present("watch") {
+        return watch(&cargo_cfg, &root_manifest, cfg);
+    }
     let workspace = Workspace::new(&root_manifest, &cargo_cfg).context(CargoConfig)?;
 
-    generate(&cargo_cfg, &workspace, cfg)
+    if cfg.split {
+        generate_split(&cargo_cfg, &workspace, cfg, None).map(|_| ())
+    } else {
+        generate(&cargo_cfg, &workspace, cfg).map(|_| ())
+    }
+}
+
+/// Exit status of the failures of the given kind, so that scripts can tell them apart.
+fn exit_code(e: &Error) -> i32 {
+    match e {
+        Error::Args { .. } => 2,
+        Error::CargoDoc { .. } | Error::CargoClean { .. } => 3,
+        Error::Cwd { .. }
+        | Error::IoRead { .. }
+        | Error::IoWrite { .. }
+        | Error::NonUtf8Path { .. }
+        | Error::PostHookSpawn { .. }
+        | Error::DumpIndex { .. }
+        | Error::NoDocDir { .. }
+        | Error::NoDocumentation { .. } => 4,
+        Error::Sqlite { .. } => 5,
+        _ => 1
+    }
 }
 
 fn main() {
@@ -90,6 +299,73 @@ fn main() {
         .subcommand(
             SubCommand::with_name("docset")
                 .about("Generates a docset")
+                .subcommand(
+                    SubCommand::with_name("list")
+                        .about("Prints the entries which would be indexed, from the existing documentation")
+                        .arg(
+                            Arg::from_usage("-p, --package <SPEC>...  'Package(s) to list'")
+                                .required(false)
+                        )
+                        .arg(
+                            Arg::from_usage("--type <KIND> 'Only list the entries of this kind, such as macro'")
+                                .multiple(true)
+                                .number_of_values(1)
+                                .required(false)
+                        )
+                        .arg(
+                            Arg::from_usage("--path <PATH> 'Only list the items located under the module path PATH'")
+                                .multiple(true)
+                                .number_of_values(1)
+                                .required(false)
+                        )
+                        .arg(
+                            Arg::from_usage("--manifest-path <PATH> 'Path to Cargo.toml'")
+                                .required(false)
+                        )
+                        .args_from_usage(
+                            "-q, --quiet             'Only print the entries, without the summary'
+                            --all                    'List all packages in the workspace'
+                            --index-members          'Also list methods, fields, variants, trait implementations and sections'"
+                        )
+                )
+                .subcommand(
+                    SubCommand::with_name("verify")
+                        .about("Checks that a generated docset is complete and usable")
+                        .args_from_usage(
+                            "<PATH>                  'Path of the .docset directory'
+                            -q, --quiet              'Only report the problems'"
+                        )
+                )
+                .subcommand(
+                    SubCommand::with_name("merge")
+                        .about("Merges existing docsets into a single one")
+                        .args_from_usage(
+                            "-o, --output <PATH>     'Path of the merged .docset directory, which is named after it'
+                            <DOCSET>...              'Paths of the .docset directories to merge'
+                            -q, --quiet              'No output printed to stdout'"
+                        )
+                )
+                .subcommand(
+                    SubCommand::with_name("clean")
+                        .about("Removes the generated docsets")
+                        .arg(
+                            Arg::from_usage("--docset-name <NAME> 'Only remove the docset with this name'")
+                                .required(false)
+                        )
+                        .arg(
+                            Arg::from_usage(
+                                "-o, --output <DIR> 'Directory in which the docsets were written (default: target/docset)'"
+                            )
+                            .required(false)
+                        )
+                        .arg(
+                            Arg::from_usage("--manifest-path <PATH> 'Path to Cargo.toml'")
+                                .required(false)
+                        )
+                        .args_from_usage(
+                            "-q, --quiet             'Suppress all output to stdout.'"
+                        )
+                )
                 .arg(
                     Arg::from_usage("-p, --package <SPEC>...  'Package(s) to document'")
                         .required(false)
@@ -101,12 +377,35 @@ fn main() {
                     .multiple(true)
                     .required(false)
                 )
+                .arg(
+                    Arg::from_usage(
+                        "--extern-url <CRATE=URL> 'Link the items of the dependency CRATE to its documentation at URL \
+                         instead of bundling it (requires a nightly toolchain)'"
+                    )
+                    .multiple(true)
+                    .number_of_values(1)
+                    .required(false)
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--only-deps <SPEC>...  'Only document these dependencies of the package(s)'"
+                    )
+                    .multiple(true)
+                    .required(false)
+                )
                 .arg(
                     Arg::from_usage(
                         "-v, --verbose  'Enable verbose output (-vv for extra verbosity)'"
                     )
                     .multiple(true)
                 )
+                .arg(
+                    Arg::from_usage(
+                        "--color <WHEN> 'Coloring of the output: auto, always or never'"
+                    )
+                    .possible_values(&["auto", "always", "never"])
+                    .required(false)
+                )
                 .arg(
                     Arg::from_usage(
                         "--bin <BIN> 'Document only the specified binary'"
@@ -114,35 +413,229 @@ fn main() {
                     .multiple(true)
                     .required(false)
                 )
+                .arg(
+                    Arg::from_usage(
+                        "--example <NAME> 'Document only the specified example'"
+                    )
+                    .multiple(true)
+                    .required(false)
+                )
                 .arg(
                     Arg::from_usage("--features <FEATURES> 'Space separated list of features to activate'")
                         .required(false)
                 )
+                .arg(
+                    Arg::from_usage("--manifest-path <PATH> 'Path to Cargo.toml'")
+                        .required(false)
+                )
+                .arg(
+                    Arg::from_usage("-j, --jobs <N> 'Number of parallel jobs, defaults to # of CPUs'")
+                        .required(false)
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--doc-dir <DIR> 'Package the rustdoc output in DIR, built without cargo, instead of documenting a package'"
+                    )
+                    .required(false)
+                )
+                .arg(
+                    Arg::from_usage("--target <TRIPLE> 'Document for the target triple'")
+                        .required(false)
+                )
+                .arg(
+                    Arg::from_usage(
+                        "-o, --output <DIR> 'Directory in which the docset is written (default: target/docset)'"
+                    )
+                    .required(false)
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--docset-name <NAME> 'Name of the docset (default: the package or workspace name)'"
+                    )
+                    .required(false)
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--rustdoc-arg <ARG> 'Extra argument passed to rustdoc for the documented crates'"
+                    )
+                    .multiple(true)
+                    .number_of_values(1)
+                    .allow_hyphen_values(true)
+                    .required(false)
+                )
+                .arg(
+                    Arg::from_usage("--theme <THEME> 'Default rustdoc theme of the docset: light, dark or ayu'")
+                        .required(false)
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--bundle-identifier <ID> 'Bundle identifier of the docset, such as com.example.mycrate (default: the docset name)'"
+                    )
+                    .required(false)
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--plist-template <PATH> 'Info.plist template, with the required {{name}}, {{identifier}} and \
+                         {{index_path}} placeholders, and the {{platform_family}}, {{keyword}} and {{optional_keys}} \
+                         placeholders, the latter replaced with the keys set by --toc, --enable-js and \
+                         --online-redirect-url'"
+                    )
+                    .required(false)
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--keyword <KEYWORD> 'Dash plugin keyword of the docset, scoping a search when typed as KEYWORD: (default: the lowercase package name)'"
+                    )
+                    .required(false)
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--platform-family <KEYWORD> 'Keyword restricting a Dash search to the docset (default: the lowercase package name)'"
+                    )
+                    .required(false)
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--skip-dir <DIR> 'Root rustdoc directory to exclude from the index (default: src, implementors)'"
+                    )
+                    .multiple(true)
+                    .number_of_values(1)
+                    .required(false)
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--include-path <PATH> 'Only index the items located under the module path PATH, such as mycrate::net'"
+                    )
+                    .multiple(true)
+                    .number_of_values(1)
+                    .required(false)
+                )
+                .arg(
+                    Arg::from_usage("--exclude-path <PATH> 'Do not index the items located under the module path PATH'")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .required(false)
+                )
+                .arg(
+                    Arg::from_usage("--exclude-type <KIND> 'Do not index the entries of this kind, such as method or field'")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .required(false)
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--name-separator <SEP> 'Separator between the path segments of the indexed names (default: ::)'"
+                    )
+                    .required(false)
+                )
+                .arg(
+                    Arg::from_usage("--entry-limit <N> 'Abort if the index grows beyond N entries'")
+                        .required(false)
+                )
+                .arg(
+                    Arg::from_usage("--dump-index <PATH> 'Also write the index entries as JSON to PATH, or to stdout if PATH is -'")
+                        .required(false)
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--temp-dir <DIR> 'Build the docset in DIR, then move it to the output directory once complete'"
+                    )
+                    .required(false)
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--post-hook <COMMAND> 'Shell command to run after a successful generation, with the docset path in $CARGO_DOCSET_PATH'"
+                    )
+                    .required(false)
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--online-redirect-url <URL> 'Base URL Dash falls back to for the pages missing from the docset, such as https://docs.rs/mycrate/1.2.3/'"
+                    )
+                    .required(false)
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--icon <PNG> 'Icon of the docset (default: docset-icon.png in the workspace root, if present)'"
+                    )
+                    .required(false)
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--icon-2x <PNG> 'High resolution icon of the docset (default: docset-icon@2x.png in the workspace root, if present)'"
+                    )
+                    .required(false)
+                )
+                .arg(
+                    Arg::from_usage(
+                        "--feed <BASE_URL> 'Also write a Dash feed for the docset, to be hosted at BASE_URL'"
+                    )
+                    .required(false)
+                )
                 .args_from_usage(
                     "-q, --quiet             'Suppress all output to stdout.'
                     -C, --no-clean           'Do not clean the doc directory before generating the rustdoc'
+                    --no-build               'Package the existing rustdoc output instead of generating it'
+                    --no-doc                 'Same as --no-build'
                     --all                    'Document all packages in the workspace'
+                    --split                  'Generate a separate docset for each documented package'
                     --lib                    'Document only this package's library'
                     --bins                   'Document all binaries'
+                    --examples               'Document all examples'
                     --no-deps                'Don't build documentation for dependencies'
+                    --linked-deps            'Only bundle the dependency pages linked from the documented crates'
                     --document-private-items 'Document private items'
+                    --no-source              'Don't bundle the source code pages in the docset'
+                    --docs-rs                'Document with the docsrs cfg set, as docs.rs does'
+                    --use-docs-rs-metadata   'Apply the [package.metadata.docs.rs] settings of the manifest'
                     --all-features           'Build with all features enabled'
                     --no-default-features    'Build without the 'default' feature'
                     --frozen                 'Require Cargo.lock and cache are up to date'
                     --locked                 'Require Cargo.lock is up to date'
-                    --offline                'Run without accessing the network'"
+                    --offline                'Run without accessing the network'
+                    --strict                 'Fail if some index entries conflict with each other'
+                    --fast-index             'Build the index in memory before writing it to disk'
+                    --check-config           'Report what would be documented without building anything'
+                    --dry-run                'Build the documentation and report what would be indexed, without writing the docset'
+                    --fix-redirects          'Point cross-crate redirects at the bundled page, or at docs.rs'
+                    --provenance             'Record the tool and compiler versions used in the docset'
+                    --versioned              'Include the package version in the docset name and identifier'
+                    --primary-only           'Only index items which have their own page'
+                    --short-aliases          'Also index the items under their bare name, ranking them first in the searches'
+                    --keep-reexports         'Also index the pages of the inlined re-exports of items'
+                    --index-members          'Also index methods, fields, variants, trait implementations and sections (slower)'
+                    --toc                    'Add Dash table of contents anchors to the type pages (slower)'
+                    --compress               'Also write a gzipped tarball of the docset'
+                    --manifest               'Also write the SHA-256 digests of the docset files to manifest.sha256'
+                    --zeal                   'Also write the metadata expected by Zeal'
+                    --watch                  'Generate the docset again each time the sources change'
+                    --fts                    'Also build a full-text search index of the page contents'
+                    --timings                'Report how long each generation step took'
+                    --enable-js              'Enable JavaScript in Dash for the docset pages, needed by the rustdoc search'
+                    --incremental            'Update the existing docset instead of generating it from scratch'
+                    --link                   'Hard link the documentation files into the docset instead of copying them when possible'
+                    --open                   'Open the docset in Dash or Zeal after generating it'"
                 )
         )
-        .get_matches();
+        .get_matches_safe();
+    let matches = match matches {
+        Ok(matches) => matches,
+        Err(e) if matches!(e.kind, ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed) => e.exit(),
+        Err(e) => {
+            // Same status as the invalid arguments reported by the generation.
+            eprintln!("{}", e.message);
+            exit(2);
+        }
+    };
     if let Some(sub_matches) = matches.subcommand_matches("docset") {
         if let Err(e) = run(sub_matches) {
             eprintln!("{}", e);
-            exit(1);
+            exit(exit_code(&e));
         }
     }
     else {
         println!("Invalid arguments.");
         println!("{}", matches.usage());
-        exit(1);
+        exit(2);
     }
 }
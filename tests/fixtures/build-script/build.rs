@@ -0,0 +1,14 @@
+use std::{env, fs, path::Path};
+
+fn main() {
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let generated = "\
+        /// A struct generated by the build script.\n\
+        pub struct Generated;\n\
+        \n\
+        /// A function generated by the build script.\n\
+        pub fn generated() -> Generated {\n\
+            Generated\n\
+        }\n";
+    fs::write(Path::new(&out_dir).join("generated.rs"), generated).unwrap();
+}
@@ -0,0 +1,8 @@
+//! Crate with a module generated by its build script.
+
+/// Items generated by the build script.
+pub mod generated {
+    include!(concat!(env!("OUT_DIR"), "/generated.rs"));
+}
+
+pub use generated::Generated;
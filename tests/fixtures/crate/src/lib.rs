@@ -0,0 +1,27 @@
+//! Geometry primitives.
+
+pub mod shapes;
+
+/// The origin of the plane.
+pub const ORIGIN: Point = Point { x: 0, y: 0 };
+
+/// A point of the plane.
+#[derive(Clone, Debug)]
+pub struct Point {
+    /// Abscissa of the point.
+    pub x: i32,
+    /// Ordinate of the point.
+    pub y: i32
+}
+
+impl Point {
+    /// Create a point.
+    pub fn new(x: i32, y: i32) -> Point {
+        Point { x, y }
+    }
+}
+
+/// Return the area of `shape`.
+pub fn area<S: shapes::Shape>(shape: &S) -> f64 {
+    shape.area()
+}
@@ -0,0 +1,16 @@
+//! Shapes of the plane.
+
+/// A shape of the plane.
+pub trait Shape {
+    /// Return the area of the shape.
+    fn area(&self) -> f64;
+}
+
+/// A line between two points.
+pub struct Line(pub crate::Point, pub crate::Point);
+
+impl Shape for Line {
+    fn area(&self) -> f64 {
+        0.
+    }
+}
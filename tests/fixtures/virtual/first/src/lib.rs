@@ -0,0 +1,4 @@
+//! First member of the virtual workspace.
+
+/// A struct of the first member.
+pub struct First;
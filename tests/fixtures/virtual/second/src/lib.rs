@@ -0,0 +1,4 @@
+//! Second member of the virtual workspace.
+
+/// A function of the second member.
+pub fn second() {}
@@ -0,0 +1,9 @@
+//! Core member of the workspace.
+
+/// Status of the application.
+pub enum Status {
+    /// The application is running.
+    Running,
+    /// The application is done.
+    Done
+}
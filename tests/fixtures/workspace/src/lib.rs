@@ -0,0 +1,6 @@
+//! Application built on the core member.
+
+/// Run the application.
+pub fn run() -> app_core::Status {
+    app_core::Status::Done
+}
@@ -0,0 +1,484 @@
+//! Generation of docsets from the workspaces and documentation directories in `tests/fixtures`.
+
+use cargo::{
+    core::{Shell, Workspace},
+    util::homedir,
+    Config as CargoConfig
+};
+use cargo_docset::{
+    generate, generate_from_doc_dir, generate_split, generate_with_hook, verify_docset, DocsetEntry, EntryType, Error,
+    GenerateConfig, GeneratedDocset, Package, Result
+};
+use rusqlite::{params, Connection};
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    process::{self, Command}
+};
+
+/// Copy the `fixture` workspace to a temporary directory named after `test`, so that the tests can
+/// build it concurrently, and return the directory.
+fn fixture(fixture: &str, test: &str) -> PathBuf {
+    fn copy_dir(src: &Path, dst: &Path) {
+        fs::create_dir_all(dst).unwrap();
+        for entry in fs::read_dir(src).unwrap() {
+            let entry = entry.unwrap();
+            let dst = dst.join(entry.file_name());
+            if entry.file_type().unwrap().is_dir() {
+                copy_dir(&entry.path(), &dst);
+            } else {
+                fs::copy(entry.path(), dst).unwrap();
+            }
+        }
+    }
+
+    let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(fixture);
+    let dst = env::temp_dir().join(format!("cargo-docset-tests-{}", process::id())).join(test);
+    if dst.exists() {
+        fs::remove_dir_all(&dst).unwrap();
+    }
+    copy_dir(&src, &dst);
+    dst
+}
+
+/// Run `cargo docset` with `args` in the workspace at `dir`, and check that it succeeds.
+fn cargo_docset(dir: &Path, args: &[&str]) {
+    cargo_docset_with_target_dir(dir, args, None)
+}
+
+/// Run `cargo docset` with `args` in the workspace at `dir`, with `CARGO_TARGET_DIR` set to
+/// `target_dir` if given, and check that it succeeds.
+fn cargo_docset_with_target_dir(dir: &Path, args: &[&str], target_dir: Option<&Path>) {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_cargo-docset"));
+    command.arg("docset").args(args).current_dir(dir);
+    if let Some(target_dir) = target_dir {
+        command.env("CARGO_TARGET_DIR", target_dir);
+    }
+    let output = command.output().unwrap();
+    assert!(output.status.success(), "cargo docset failed:\n{}", String::from_utf8_lossy(&output.stderr));
+}
+
+/// Cargo configuration of the workspace at `dir`, which does not print anything.
+fn cargo_config(dir: &Path) -> CargoConfig {
+    let shell = Shell::from_write(Box::new(io::sink()));
+    CargoConfig::new(shell, dir.to_owned(), homedir(dir).unwrap())
+}
+
+/// Generation settings writing the docset of the workspace at `dir` to `dir/docset`.
+fn config(dir: &Path) -> GenerateConfig {
+    GenerateConfig {
+        output_dir: Some(dir.join("docset")),
+        ..GenerateConfig::default()
+    }
+}
+
+/// Generate the docset of the workspace at `dir` with `cfg`.
+fn generate_at(dir: &Path, cfg: GenerateConfig) -> Result<GeneratedDocset> {
+    let cargo_cfg = cargo_config(dir);
+    let workspace = Workspace::new(&dir.join("Cargo.toml"), &cargo_cfg).unwrap();
+    generate(&cargo_cfg, &workspace, cfg)
+}
+
+/// Generate the split docsets of the workspace at `dir` with `cfg`.
+fn generate_split_at(dir: &Path, cfg: GenerateConfig) -> Result<Vec<GeneratedDocset>> {
+    let cargo_cfg = cargo_config(dir);
+    let workspace = Workspace::new(&dir.join("Cargo.toml"), &cargo_cfg).unwrap();
+    generate_split(&cargo_cfg, &workspace, cfg, None)
+}
+
+/// Return the `(name, type, path)` index entries of the docset at `docset`, sorted.
+fn index_entries(docset: &Path) -> Vec<(String, String, String)> {
+    let conn = Connection::open(docset.join("Contents").join("Resources").join("docSet.dsidx")).unwrap();
+    let mut stmt = conn.prepare("SELECT name, type, path FROM searchIndex").unwrap();
+    let mut entries: Vec<_> = stmt
+        .query_map(params![], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .map(|row| row.unwrap())
+        .collect();
+    entries.sort();
+    entries
+}
+
+/// Return the path of the index page of the docset at `docset`, relative to its documentation
+/// directory, as read from its Info.plist.
+fn index_file_path(docset: &Path) -> String {
+    let info_plist = fs::read_to_string(docset.join("Contents").join("Info.plist")).unwrap();
+    let key = "<key>dashIndexFilePath</key>";
+    let value = info_plist[info_plist.find(key).unwrap() + key.len()..].trim_start();
+    let value = value.strip_prefix("<string>").unwrap();
+    value[..value.find("</string>").unwrap()].to_owned()
+}
+
+/// Path of the documentation directory of the docset at `docset`.
+fn documents_dir(docset: &Path) -> PathBuf {
+    docset.join("Contents").join("Resources").join("Documents")
+}
+
+/// Check that the docset at `docset` opens on `index_file_path`, which is bundled, and that it
+/// passes the verification.
+fn assert_index_file_path(dir: &Path, docset: &Path, expected: &str) {
+    assert_eq!(index_file_path(docset), expected);
+    assert!(documents_dir(docset).join(expected).is_file());
+    verify_docset(&cargo_config(dir), docset).unwrap();
+}
+
+/// Return whether the docset at `docset` has an index entry of type `ty` named `name`.
+fn has_entry(docset: &Path, name: &str, ty: &str) -> bool {
+    index_entries(docset).iter().any(|(n, t, _)| n == name && t == ty)
+}
+
+#[test]
+fn build_script_generated_module() {
+    let dir = fixture("build-script", "build-script");
+    cargo_docset(&dir, &[]);
+    let docset = dir.join("target").join("docset").join("codegen.docset");
+    let entries = index_entries(&docset);
+    let entry = |name: &str, ty: &str, path: &str| (name.to_owned(), ty.to_owned(), path.to_owned());
+    for expected in &[
+        entry("codegen::generated::Generated", "Struct", "codegen/generated/struct.Generated.html"),
+        entry("codegen::generated::generated", "Function", "codegen/generated/fn.generated.html")
+    ] {
+        assert!(entries.contains(expected), "missing entry {:?}", expected);
+    }
+    // The re-export has no page of its own.
+    assert!(!has_entry(&docset, "codegen::Generated", "Struct"));
+}
+
+#[test]
+fn crate_docset_target_dir() {
+    let dir = fixture("crate", "crate-target-dir");
+    let target_dir = dir.join("custom-target");
+    cargo_docset_with_target_dir(&dir, &[], Some(&target_dir));
+    assert!(target_dir.join("doc").join("geo").join("index.html").is_file());
+    let docset = target_dir.join("docset").join("geo.docset");
+    assert!(has_entry(&docset, "geo::Point", "Struct"));
+    assert!(!dir.join("target").exists());
+    // The existing documentation is read from the target directory too.
+    fs::remove_dir_all(&docset).unwrap();
+    cargo_docset_with_target_dir(&dir, &["--no-build"], Some(&target_dir));
+    assert!(has_entry(&docset, "geo::Point", "Struct"));
+}
+
+#[test]
+fn crate_docset_stale_docs() {
+    let dir = fixture("crate", "crate-stale-docs");
+    generate_at(&dir, config(&dir)).unwrap();
+    let lib = dir.join("src").join("lib.rs");
+    fs::write(&lib, fs::read_to_string(&lib).unwrap()).unwrap();
+    let cfg = GenerateConfig {
+        no_build: true,
+        ..config(&dir)
+    };
+    match generate_at(&dir, cfg) {
+        Err(Error::StaleDocs { crate_name, path }) => {
+            assert_eq!(crate_name, "geo");
+            assert_eq!(path, lib);
+        }
+        result => panic!("unexpected result {:?}", result.map(|generated| generated.path))
+    }
+}
+
+#[test]
+fn crate_docset_constants() {
+    let dir = fixture("crate", "crate-constants");
+    cargo_docset(&dir, &[]);
+    let docset = dir.join("target").join("docset").join("geo.docset");
+    let expected = ("geo::ORIGIN".to_owned(), "Constant".to_owned(), "geo/constant.ORIGIN.html".to_owned());
+    assert!(index_entries(&docset).contains(&expected));
+}
+
+#[test]
+fn workspace_docset_has_a_package_entry_per_member() {
+    let dir = fixture("workspace", "workspace-packages");
+    cargo_docset(&dir, &["--all"]);
+    let docset = dir.join("target").join("docset").join("workspace-packages.docset");
+    let packages = index_entries(&docset)
+        .into_iter()
+        .filter(|(_, ty, _)| ty == "Package")
+        .map(|(name, _, path)| (name, path))
+        .collect::<Vec<_>>();
+    assert_eq!(packages, vec![
+        ("app".to_owned(), "app/index.html".to_owned()),
+        ("app_core".to_owned(), "app_core/index.html".to_owned())
+    ]);
+    assert!(has_entry(&docset, "app::run", "Function"));
+    assert!(has_entry(&docset, "app_core::Status", "Enum"));
+}
+
+#[test]
+fn crate_docset() {
+    let dir = fixture("crate", "crate");
+    let generated = generate_at(&dir, config(&dir)).unwrap();
+    assert_eq!(generated.path, dir.join("docset").join("geo.docset"));
+    assert_index_file_path(&dir, &generated.path, "geo/index.html");
+    let entries = index_entries(&generated.path);
+    let entry = |name: &str, ty: &str, path: &str| (name.to_owned(), ty.to_owned(), path.to_owned());
+    for expected in &[
+        entry("geo", "Package", "geo/index.html"),
+        entry("geo::shapes", "Module", "geo/shapes/index.html"),
+        entry("geo::ORIGIN", "Constant", "geo/constant.ORIGIN.html"),
+        entry("geo::Point", "Struct", "geo/struct.Point.html"),
+        entry("geo::area", "Function", "geo/fn.area.html"),
+        entry("geo::shapes::Shape", "Trait", "geo/shapes/trait.Shape.html"),
+        entry("geo::shapes::Line", "Struct", "geo/shapes/struct.Line.html")
+    ] {
+        assert!(entries.contains(expected), "missing entry {:?}", expected);
+    }
+    for (_, _, path) in &entries {
+        assert!(documents_dir(&generated.path).join(path).is_file(), "missing page {}", path);
+    }
+    let conn = Connection::open(generated.path.join("Contents").join("Resources").join("docSet.dsidx")).unwrap();
+    let sql: String = conn
+        .query_row("SELECT sql FROM sqlite_master WHERE type = 'index' AND name = 'anchor'", params![], |row| row.get(0))
+        .unwrap();
+    assert_eq!(sql, "CREATE UNIQUE INDEX anchor ON searchIndex (name, type, path)");
+}
+
+#[test]
+fn crate_docset_skip_dirs() {
+    let dir = fixture("crate", "crate-skip-dirs");
+    let mut cfg = config(&dir);
+    cfg.skip_dirs.push("geo".to_owned());
+    let generated = generate_at(&dir, cfg).unwrap();
+    // The skipped directories are not indexed, but they are still bundled.
+    assert!(index_entries(&generated.path).iter().all(|(_, _, path)| !path.starts_with("geo/")));
+    assert!(documents_dir(&generated.path).join("geo").join("struct.Point.html").is_file());
+}
+
+#[test]
+fn crate_docset_entry_hook() {
+    let dir = fixture("crate", "crate-entry-hook");
+    let cargo_cfg = cargo_config(&dir);
+    let workspace = Workspace::new(&dir.join("Cargo.toml"), &cargo_cfg).unwrap();
+    // Drop the functions, and index the constants as global variables.
+    let hook = |entries: &mut Vec<DocsetEntry>| {
+        entries.retain(|entry| entry.ty != EntryType::Function);
+        for entry in entries.iter_mut().filter(|entry| entry.ty == EntryType::Constant) {
+            entry.ty = EntryType::Static;
+            entry.name = entry.name.to_lowercase();
+        }
+    };
+    let generated = generate_with_hook(&cargo_cfg, &workspace, config(&dir), Some(&hook)).unwrap();
+    let entries = index_entries(&generated.path);
+    assert!(entries.iter().all(|(_, ty, _)| ty != "Function" && ty != "Constant"));
+    let origin = ("geo::origin".to_owned(), "Global".to_owned(), "geo/constant.ORIGIN.html".to_owned());
+    assert!(entries.contains(&origin), "missing entry {:?}", origin);
+    assert!(entries.iter().any(|(name, ty, _)| name == "geo::Point" && ty == "Struct"));
+}
+
+#[test]
+fn versioned_docsets_coexist() {
+    let dir = fixture("crate", "crate-versioned");
+    let cfg = GenerateConfig {
+        versioned: true,
+        online_redirect_url: Some("https://docs.rs/geo/latest/geo/".to_owned()),
+        ..config(&dir)
+    };
+    let first = generate_at(&dir, cfg.clone()).unwrap();
+    let manifest = fs::read_to_string(dir.join("Cargo.toml")).unwrap();
+    fs::write(dir.join("Cargo.toml"), manifest.replace("version = \"0.1.0\"", "version = \"0.2.0\"")).unwrap();
+    let second = generate_at(&dir, cfg).unwrap();
+    assert_eq!(first.path, dir.join("docset").join("geo 0.1.0.docset"));
+    assert_eq!(second.path, dir.join("docset").join("geo 0.2.0.docset"));
+    for (docset, version) in &[(&first.path, "0.1.0"), (&second.path, "0.2.0")] {
+        let info_plist = fs::read_to_string(docset.join("Contents").join("Info.plist")).unwrap();
+        assert!(info_plist.contains(&format!("<string>https://docs.rs/geo/{}/</string>", version)));
+        assert!(has_entry(docset, "geo::Point", "Struct"));
+    }
+}
+
+/// Return the `(id, name, type, path)` rows of the index of the docset at `docset`, by id.
+fn index_rows(docset: &Path) -> Vec<(i64, String, String, String)> {
+    let conn = Connection::open(docset.join("Contents").join("Resources").join("docSet.dsidx")).unwrap();
+    let mut stmt = conn.prepare("SELECT id, name, type, path FROM searchIndex ORDER BY id").unwrap();
+    let rows = stmt
+        .query_map(params![], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+        .unwrap()
+        .map(|row| row.unwrap())
+        .collect();
+    rows
+}
+
+#[test]
+fn crate_docset_is_deterministic() {
+    let dir = fixture("crate", "crate-deterministic");
+    let cfg = GenerateConfig {
+        index_members: true,
+        ..config(&dir)
+    };
+    let first = index_rows(&generate_at(&dir, cfg.clone()).unwrap().path);
+    let second = index_rows(&generate_at(&dir, cfg).unwrap().path);
+    assert!(!first.is_empty());
+    assert_eq!(first, second);
+}
+
+/// Return the targets of the links to the source pages in the HTML pages located under `dir`,
+/// resolved relatively to their page.
+fn source_links(dir: &Path) -> Vec<PathBuf> {
+    let mut links = vec![];
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            links.extend(source_links(&path));
+        } else if path.extension().map_or(false, |extension| extension == "html") {
+            let contents = fs::read_to_string(&path).unwrap();
+            for href in contents.split("href=\"").skip(1).map(|s| &s[..s.find('"').unwrap()]) {
+                let target = href.split('#').next().unwrap();
+                if target.contains("src/") && !target.contains("://") {
+                    links.push(path.parent().unwrap().join(target));
+                }
+            }
+        }
+    }
+    links
+}
+
+#[test]
+fn crate_docset_source_pages() {
+    let dir = fixture("crate", "crate-source");
+    let generated = generate_at(&dir, config(&dir)).unwrap();
+    let links = source_links(&documents_dir(&generated.path).join("geo"));
+    assert!(!links.is_empty());
+    for link in &links {
+        assert!(link.is_file(), "broken source link {}", link.display());
+    }
+    // The source pages are bundled, but not indexed.
+    assert!(index_entries(&generated.path).iter().all(|(_, _, path)| !path.starts_with("src/")));
+
+    let cfg = GenerateConfig {
+        no_source: true,
+        ..config(&dir)
+    };
+    let generated = generate_at(&dir, cfg).unwrap();
+    assert!(!documents_dir(&generated.path).join("src").exists());
+}
+
+#[test]
+fn renamed_crate_docset_index_file_path() {
+    let dir = fixture("crate", "crate-renamed");
+    let cfg = GenerateConfig {
+        docset_name: Some("Geometry".to_owned()),
+        ..config(&dir)
+    };
+    let generated = generate_at(&dir, cfg).unwrap();
+    assert_eq!(generated.path, dir.join("docset").join("Geometry.docset"));
+    assert_index_file_path(&dir, &generated.path, "geo/index.html");
+}
+
+#[test]
+fn crate_docset_members() {
+    let dir = fixture("crate", "crate-members");
+    let generated = generate_at(&dir, config(&dir)).unwrap();
+    // The members are only indexed on demand.
+    assert!(!has_entry(&generated.path, "geo::Point::new", "Method"));
+
+    let cfg = GenerateConfig {
+        index_members: true,
+        ..config(&dir)
+    };
+    let generated = generate_at(&dir, cfg).unwrap();
+    assert!(has_entry(&generated.path, "geo::Point::new", "Method"));
+    assert!(has_entry(&generated.path, "geo::Point::x", "Field"));
+    assert!(has_entry(&generated.path, "geo::shapes::Shape::area", "Method"));
+    // The trait implementations are indexed under the implementing type, and their methods under
+    // the trait only.
+    assert!(has_entry(&generated.path, "geo::Point: Clone", "Instance"));
+    assert!(has_entry(&generated.path, "geo::shapes::Line: Shape", "Instance"));
+    assert!(!has_entry(&generated.path, "geo::shapes::Line::area", "Method"));
+}
+
+#[test]
+fn workspace_docset_index_file_path() {
+    // The workspace is named after its directory, and the docsets after the documented packages.
+    let dir = fixture("workspace", "workspace-index");
+    let generated = generate_at(&dir, config(&dir)).unwrap();
+    assert_eq!(generated.path, dir.join("docset").join("app.docset"));
+    assert_index_file_path(&dir, &generated.path, "app/index.html");
+
+    let cfg = GenerateConfig {
+        package: Package::All,
+        ..config(&dir)
+    };
+    let generated = generate_at(&dir, cfg).unwrap();
+    assert_eq!(generated.path, dir.join("docset").join("workspace-index.docset"));
+    assert_index_file_path(&dir, &generated.path, "app/index.html");
+
+    // The documentation directory of a crate is named after the crate, not after its package.
+    let cfg = GenerateConfig {
+        package: Package::Single("app-core".to_owned()),
+        ..config(&dir)
+    };
+    let generated = generate_at(&dir, cfg).unwrap();
+    assert_eq!(generated.path, dir.join("docset").join("app-core.docset"));
+    assert_index_file_path(&dir, &generated.path, "app_core/index.html");
+}
+
+#[test]
+fn virtual_workspace_documents_all_members() {
+    let dir = fixture("virtual", "virtual-all");
+    let generated = generate_at(&dir, config(&dir)).unwrap();
+    assert_eq!(generated.path, dir.join("docset").join("virtual-all.docset"));
+    // The docset opens on the first member.
+    assert_index_file_path(&dir, &generated.path, "first/index.html");
+    assert!(has_entry(&generated.path, "first::First", "Struct"));
+    assert!(has_entry(&generated.path, "second::second", "Function"));
+}
+
+#[test]
+fn virtual_workspace_exclude_requires_all() {
+    let dir = fixture("virtual", "virtual-exclude");
+    let cfg = GenerateConfig {
+        exclude: vec!["second".to_owned()],
+        ..config(&dir)
+    };
+    match generate_at(&dir, cfg) {
+        Err(Error::Args { msg }) => assert_eq!(msg, "--exclude must be used with --all"),
+        other => panic!("unexpected result: {:?}", other.map(|generated| generated.path))
+    }
+
+    let cfg = GenerateConfig {
+        package: Package::All,
+        exclude: vec!["second".to_owned()],
+        ..config(&dir)
+    };
+    let generated = generate_at(&dir, cfg).unwrap();
+    assert!(has_entry(&generated.path, "first::First", "Struct"));
+    assert!(!has_entry(&generated.path, "second::second", "Function"));
+}
+
+#[test]
+fn virtual_workspace_split() {
+    let dir = fixture("virtual", "virtual-split");
+    let generated = generate_split_at(&dir, config(&dir)).unwrap();
+    let paths: Vec<_> = generated.iter().map(|generated| generated.path.clone()).collect();
+    assert_eq!(paths, vec![dir.join("docset").join("first.docset"), dir.join("docset").join("second.docset")]);
+    assert_index_file_path(&dir, &paths[0], "first/index.html");
+    assert_index_file_path(&dir, &paths[1], "second/index.html");
+    assert!(has_entry(&paths[0], "first::First", "Struct"));
+    assert!(!has_entry(&paths[0], "second::second", "Function"));
+    assert!(has_entry(&paths[1], "second::second", "Function"));
+    assert!(!has_entry(&paths[1], "first::First", "Struct"));
+}
+
+#[test]
+fn doc_dir_primitive_pages() {
+    let dir = fixture("doc-dir", "doc-dir-primitive");
+    let cfg = GenerateConfig {
+        doc_dir: Some(dir.join("doc")),
+        docset_name: Some("core".to_owned()),
+        index_members: true,
+        ..config(&dir)
+    };
+    let generated = generate_from_doc_dir(&cargo_config(&dir), cfg, None).unwrap();
+    assert_eq!(generated.path, dir.join("docset").join("core.docset"));
+    let entries = index_entries(&generated.path);
+    let entry = |name: &str, ty: &str, path: &str| (name.to_owned(), ty.to_owned(), path.to_owned());
+    for expected in &[
+        entry("core::u32", "Builtin", "core/primitive.u32.html"),
+        entry("core::u32::MAX", "Constant", "core/primitive.u32.html#associatedconstant.MAX"),
+        entry("core::u32::count_ones", "Method", "core/primitive.u32.html#method.count_ones")
+    ] {
+        assert!(entries.contains(expected), "missing entry {:?}", expected);
+    }
+}